@@ -1,6 +1,7 @@
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     Number(f64),
+    Str(String),
     Variable(String),
     Binary {
         op: BinaryOp,
@@ -15,6 +16,19 @@ pub enum Expr {
         func: String,
         args: Vec<Expr>,
     },
+    Cond {
+        test: Box<Expr>,
+        then: Box<Expr>,
+        else_: Box<Expr>,
+    },
+    /// An operator referenced as a value, e.g. `\+`, rather than applied
+    /// infix — parses today so the grammar is in place for a future
+    /// higher-order builtin (e.g. `reduce(\+, 0, list)`) to receive an
+    /// operator as an argument. No such builtin exists yet and every
+    /// compiler backend rejects `OpFunc` wherever it appears (see
+    /// `compiler.rs`'s `Expr::OpFunc` arms), so this is disclosed groundwork,
+    /// not a usable feature yet.
+    OpFunc(BinaryOp),
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -24,11 +38,100 @@ pub enum BinaryOp {
     Mul,
     Div,
     Pow,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+}
+
+impl BinaryOp {
+    /// The source symbol for this operator, e.g. `Add` -> `"+"`. Used to
+    /// render an `\op` reference back as source text in error messages.
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Pow => "^",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::And => "&&",
+            BinaryOp::Or => "||",
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum UnaryOp {
     Neg,
+    Not,
+}
+
+impl Expr {
+    /// Visits every node in this tree, depth-first pre-order (a node before
+    /// its children), calling `f` on each one. If `f` returns `false`, that
+    /// node's children are skipped, but the walk continues with whatever
+    /// comes after it (e.g. a later argument, or the rest of the tree).
+    ///
+    /// Lets tooling inspect a parsed expression — collect referenced
+    /// variables, check which builtins it calls, measure its depth — without
+    /// hand-writing a recursive match or cloning/recompiling it.
+    pub fn walk(&self, f: &mut impl FnMut(&Expr) -> bool) {
+        if !f(self) {
+            return;
+        }
+        match self {
+            Expr::Number(_) | Expr::Str(_) | Expr::Variable(_) | Expr::OpFunc(_) => {}
+            Expr::Binary { left, right, .. } => {
+                left.walk(f);
+                right.walk(f);
+            }
+            Expr::Unary { expr, .. } => expr.walk(f),
+            Expr::Call { args, .. } => {
+                for arg in args {
+                    arg.walk(f);
+                }
+            }
+            Expr::Cond { test, then, else_ } => {
+                test.walk(f);
+                then.walk(f);
+                else_.walk(f);
+            }
+        }
+    }
+
+    /// The distinct variable names referenced anywhere in this expression.
+    pub fn free_variables(&self) -> std::collections::BTreeSet<String> {
+        let mut names = std::collections::BTreeSet::new();
+        self.walk(&mut |node| {
+            if let Expr::Variable(name) = node {
+                names.insert(name.clone());
+            }
+            true
+        });
+        names
+    }
+
+    /// The distinct function names called anywhere in this expression.
+    pub fn used_functions(&self) -> std::collections::BTreeSet<String> {
+        let mut names = std::collections::BTreeSet::new();
+        self.walk(&mut |node| {
+            if let Expr::Call { func, .. } = node {
+                names.insert(func.clone());
+            }
+            true
+        });
+        names
+    }
 }
 
 #[cfg(test)]
@@ -100,11 +203,33 @@ mod tests {
     fn test_binary_op_equality() {
         assert_eq!(BinaryOp::Add, BinaryOp::Add);
         assert_ne!(BinaryOp::Add, BinaryOp::Sub);
+        assert_eq!(BinaryOp::Lt, BinaryOp::Lt);
+        assert_ne!(BinaryOp::And, BinaryOp::Or);
     }
 
     #[test]
     fn test_unary_op_equality() {
         assert_eq!(UnaryOp::Neg, UnaryOp::Neg);
+        assert_ne!(UnaryOp::Neg, UnaryOp::Not);
+    }
+
+    #[test]
+    fn test_expr_cond() {
+        // x > 0 ? x : -x
+        let expr = Expr::Cond {
+            test: Box::new(Expr::Binary {
+                op: BinaryOp::Gt,
+                left: Box::new(Expr::Variable("x".to_string())),
+                right: Box::new(Expr::Number(0.0)),
+            }),
+            then: Box::new(Expr::Variable("x".to_string())),
+            else_: Box::new(Expr::Unary {
+                op: UnaryOp::Neg,
+                expr: Box::new(Expr::Variable("x".to_string())),
+            }),
+        };
+
+        assert!(matches!(expr, Expr::Cond { .. }));
     }
 
     #[test]
@@ -152,4 +277,89 @@ mod tests {
             panic!("Expected Call expression");
         }
     }
+
+    #[test]
+    fn test_walk_visits_every_node() {
+        // (x + 1) * sin(y)
+        let expr = Expr::Binary {
+            op: BinaryOp::Mul,
+            left: Box::new(Expr::Binary {
+                op: BinaryOp::Add,
+                left: Box::new(Expr::Variable("x".to_string())),
+                right: Box::new(Expr::Number(1.0)),
+            }),
+            right: Box::new(Expr::Call {
+                func: "sin".to_string(),
+                args: vec![Expr::Variable("y".to_string())],
+            }),
+        };
+
+        let mut count = 0;
+        expr.walk(&mut |_| {
+            count += 1;
+            true
+        });
+        // mul, add, x, 1, call, y
+        assert_eq!(count, 6);
+    }
+
+    #[test]
+    fn test_walk_stops_descending_when_callback_returns_false() {
+        // sin(x + 1)
+        let expr = Expr::Call {
+            func: "sin".to_string(),
+            args: vec![Expr::Binary {
+                op: BinaryOp::Add,
+                left: Box::new(Expr::Variable("x".to_string())),
+                right: Box::new(Expr::Number(1.0)),
+            }],
+        };
+
+        let mut visited = Vec::new();
+        expr.walk(&mut |node| {
+            visited.push(node.clone());
+            !matches!(node, Expr::Call { .. })
+        });
+
+        // The Call node itself is visited, but returning false for it skips
+        // descending into its argument.
+        assert_eq!(visited.len(), 1);
+    }
+
+    #[test]
+    fn test_free_variables() {
+        let expr = Expr::Cond {
+            test: Box::new(Expr::Binary {
+                op: BinaryOp::Gt,
+                left: Box::new(Expr::Variable("x".to_string())),
+                right: Box::new(Expr::Number(0.0)),
+            }),
+            then: Box::new(Expr::Variable("x".to_string())),
+            else_: Box::new(Expr::Variable("y".to_string())),
+        };
+
+        let vars: Vec<String> = expr.free_variables().into_iter().collect();
+        assert_eq!(vars, vec!["x".to_string(), "y".to_string()]);
+    }
+
+    #[test]
+    fn test_used_functions() {
+        let expr = Expr::Binary {
+            op: BinaryOp::Add,
+            left: Box::new(Expr::Call {
+                func: "sin".to_string(),
+                args: vec![Expr::Variable("x".to_string())],
+            }),
+            right: Box::new(Expr::Call {
+                func: "cos".to_string(),
+                args: vec![Expr::Call {
+                    func: "sin".to_string(),
+                    args: vec![Expr::Variable("y".to_string())],
+                }],
+            }),
+        };
+
+        let funcs: Vec<String> = expr.used_functions().into_iter().collect();
+        assert_eq!(funcs, vec!["cos".to_string(), "sin".to_string()]);
+    }
 }
@@ -14,6 +14,97 @@ pub enum OpCode {
     Pow = 6,
     Neg = 7,
     Call = 8,       // [u16: func_idx, u8: arg_count]
+    Lt = 9,
+    Gt = 10,
+    Le = 11,
+    Ge = 12,
+    Eq = 13,
+    Ne = 14,
+    And = 15,
+    Or = 16,
+    Not = 17,
+    Jump = 18,         // [u16: absolute instruction index]
+    JumpIfFalse = 19,  // [u16: absolute instruction index]
+    CallUser = 20,     // [u16: user_func_idx, u8: arg_count]
+
+    /// Short-operand forms, emitted by `Compiler::emit_load_const`/
+    /// `emit_load_var`/`emit_call` whenever the index fits in a `u8` (true
+    /// for any program with <256 constants/vars/functions, which covers
+    /// nearly every real expression). Halves the operand bytes versus the
+    /// `u16` forms above and keeps the hot loop's working set smaller.
+    LoadConstU8 = 21, // [u8: const_idx]
+    LoadVarU8 = 22,   // [u8: var_idx]
+    CallU8 = 23,      // [u8: func_idx, u8: arg_count]
+
+    /// Zero-argument stochastic sampling, compiled directly from a bare
+    /// `rand()`/`randn()` call rather than going through the `func_table`
+    /// (a `BuiltinFn` is a pure `fn(&[f64]) -> f64`, with nowhere to carry
+    /// mutable RNG state). No operand; the VM draws from its own PCG stream
+    /// on each dispatch.
+    Rand = 24,  // uniform sample in [0, 1)
+    Randn = 25, // standard normal sample (Box-Muller)
+
+    /// Internal scratch slots, distinct from `var_names` (which are always
+    /// caller-supplied `Context` inputs). Emitted by `Compiler::compile_multi`
+    /// to hash-cons a subexpression shared across several outputs: the first
+    /// occurrence is computed normally, stored here, and reloaded; every
+    /// later occurrence just loads it instead of recomputing the subtree.
+    StoreLocal = 26,   // [u16: local_idx], pops the top of stack
+    LoadLocal = 27,    // [u16: local_idx], pushes a copy
+    StoreLocalU8 = 28, // [u8: local_idx]
+    LoadLocalU8 = 29,  // [u8: local_idx]
+
+    /// Stack-manipulation primitives with no operand, used by the
+    /// single-expression common-subexpression pass in `Compiler::compile_expr`
+    /// (distinct from `StoreLocal`/`LoadLocal`'s `compile_multi` hash-consing):
+    /// when a repeated subexpression's value is still on top of the stack,
+    /// `Dup` reuses it in place of recompiling the subtree. `Swap` and `Pop`
+    /// round out the set for anything that needs to reorder or discard a
+    /// value already on the stack.
+    Dup = 30,  // duplicates the top of stack
+    Swap = 31, // exchanges the top two stack values
+    Pop = 32,  // discards the top of stack
+}
+
+impl OpCode {
+    pub(crate) fn from_u8(byte: u8) -> Option<OpCode> {
+        match byte {
+            0 => Some(OpCode::LoadConst),
+            1 => Some(OpCode::LoadVar),
+            2 => Some(OpCode::Add),
+            3 => Some(OpCode::Sub),
+            4 => Some(OpCode::Mul),
+            5 => Some(OpCode::Div),
+            6 => Some(OpCode::Pow),
+            7 => Some(OpCode::Neg),
+            8 => Some(OpCode::Call),
+            9 => Some(OpCode::Lt),
+            10 => Some(OpCode::Gt),
+            11 => Some(OpCode::Le),
+            12 => Some(OpCode::Ge),
+            13 => Some(OpCode::Eq),
+            14 => Some(OpCode::Ne),
+            15 => Some(OpCode::And),
+            16 => Some(OpCode::Or),
+            17 => Some(OpCode::Not),
+            18 => Some(OpCode::Jump),
+            19 => Some(OpCode::JumpIfFalse),
+            20 => Some(OpCode::CallUser),
+            21 => Some(OpCode::LoadConstU8),
+            22 => Some(OpCode::LoadVarU8),
+            23 => Some(OpCode::CallU8),
+            24 => Some(OpCode::Rand),
+            25 => Some(OpCode::Randn),
+            26 => Some(OpCode::StoreLocal),
+            27 => Some(OpCode::LoadLocal),
+            28 => Some(OpCode::StoreLocalU8),
+            29 => Some(OpCode::LoadLocalU8),
+            30 => Some(OpCode::Dup),
+            31 => Some(OpCode::Swap),
+            32 => Some(OpCode::Pop),
+            _ => None,
+        }
+    }
 }
 
 /// Function signature for built-in functions
@@ -40,28 +131,58 @@ impl FunctionMetadata {
     }
 }
 
+/// Default maximum call depth for user-defined function invocations
+/// (`OpCode::CallUser`), guarding against unbounded/recursive definitions.
+pub const DEFAULT_MAX_CALL_DEPTH: u32 = 256;
+
 /// Compiled program with optimized bytecode
 #[derive(Clone)]
 pub struct Program {
     /// Compact instruction stream: [opcode, operands...]
     pub instructions: Vec<u8>,
-    
+
     /// Constant pool for numeric literals
     pub constants: Vec<f64>,
-    
+
     /// Variable name mapping (index -> name)
     pub var_names: Vec<String>,
-    
+
     /// Function pointer table for O(1) function dispatch
     pub func_table: Vec<BuiltinFn>,
-    
+
     /// Function names for debugging
     pub func_names: Vec<String>,
-    
+
     /// Function metadata for validation
     pub func_metadata: Vec<FunctionMetadata>,
+
+    /// User-defined function bodies, indexed by `OpCode::CallUser`'s operand.
+    /// Each sub-program's `var_names` are the callee's parameters, bound by
+    /// position when the call is dispatched.
+    pub user_funcs: Vec<Program>,
+
+    /// Arity metadata for `user_funcs`, parallel to it.
+    pub user_func_metadata: Vec<FunctionMetadata>,
+
+    /// Maximum nested depth for `OpCode::CallUser` dispatch, to guard
+    /// against unbounded/recursive definitions.
+    pub max_call_depth: u32,
+
+    /// Number of `OpCode::StoreLocal`/`LoadLocal` scratch slots this program
+    /// uses, sized by `Compiler::compile_multi`'s hash-consing pass. Zero for
+    /// every ordinarily-compiled (single-expression) program.
+    pub local_count: usize,
+
+    /// Number of values `VM::run_multi` expects to find on the stack once
+    /// the instruction stream finishes — one per output expression passed to
+    /// `Compiler::compile_multi`. Always `1` for a single-expression program,
+    /// matching what `VM::run` pops.
+    pub output_count: usize,
 }
 
+/// Binary format version for `Program::to_bytes`/`Program::from_bytes`.
+const PROGRAM_FORMAT_VERSION: u32 = 1;
+
 impl Program {
     pub fn new() -> Self {
         Self {
@@ -71,10 +192,376 @@ impl Program {
             func_table: Vec::new(),
             func_names: Vec::new(),
             func_metadata: Vec::new(),
+            user_funcs: Vec::new(),
+            user_func_metadata: Vec::new(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            local_count: 0,
+            output_count: 1,
+        }
+    }
+
+    /// Produces a textual VM assembly listing, one line per instruction,
+    /// e.g. `0000 LoadConst 0 ; 42`. Resolves operands the same way the
+    /// VM does (constant/variable/function tables); an unrecognized opcode
+    /// byte (e.g. from corrupted bytecode) ends the listing with an error
+    /// line instead of panicking.
+    pub fn disassemble(&self) -> String {
+        let mut out = String::new();
+        let mut pc = 0;
+
+        while pc < self.instructions.len() {
+            let offset = pc;
+            let byte = self.instructions[pc];
+            pc += 1;
+
+            let opcode = match OpCode::from_u8(byte) {
+                Some(op) => op,
+                None => {
+                    out.push_str(&format!(
+                        "{:04} ; {}\n",
+                        offset,
+                        crate::error::ErrorKind::UnknownOpcode(byte)
+                    ));
+                    break;
+                }
+            };
+
+            let line = match opcode {
+                OpCode::LoadConst => {
+                    let idx = self.read_u16_at(pc);
+                    pc += 2;
+                    let value = self.constants.get(idx as usize).copied();
+                    match value {
+                        Some(v) => format!("LoadConst {} ; {}", idx, v),
+                        None => format!("LoadConst {} ; ?", idx),
+                    }
+                }
+                OpCode::LoadConstU8 => {
+                    let idx = self.instructions[pc] as u16;
+                    pc += 1;
+                    let value = self.constants.get(idx as usize).copied();
+                    match value {
+                        Some(v) => format!("LoadConstU8 {} ; {}", idx, v),
+                        None => format!("LoadConstU8 {} ; ?", idx),
+                    }
+                }
+                OpCode::LoadVar => {
+                    let idx = self.read_u16_at(pc);
+                    pc += 2;
+                    let name = self.var_names.get(idx as usize).map(String::as_str).unwrap_or("?");
+                    format!("LoadVar {} ; {}", idx, name)
+                }
+                OpCode::LoadVarU8 => {
+                    let idx = self.instructions[pc] as u16;
+                    pc += 1;
+                    let name = self.var_names.get(idx as usize).map(String::as_str).unwrap_or("?");
+                    format!("LoadVarU8 {} ; {}", idx, name)
+                }
+                OpCode::Call => {
+                    let idx = self.read_u16_at(pc);
+                    pc += 2;
+                    let arg_count = self.instructions[pc];
+                    pc += 1;
+                    let name = self.func_names.get(idx as usize).map(String::as_str).unwrap_or("?");
+                    format!("Call {},{} ; {}/{}", idx, arg_count, name, arg_count)
+                }
+                OpCode::CallU8 => {
+                    let idx = self.instructions[pc] as u16;
+                    pc += 1;
+                    let arg_count = self.instructions[pc];
+                    pc += 1;
+                    let name = self.func_names.get(idx as usize).map(String::as_str).unwrap_or("?");
+                    format!("CallU8 {},{} ; {}/{}", idx, arg_count, name, arg_count)
+                }
+                OpCode::CallUser => {
+                    let idx = self.read_u16_at(pc);
+                    pc += 2;
+                    let arg_count = self.instructions[pc];
+                    pc += 1;
+                    format!("CallUser {},{} ; user_func[{}]/{}", idx, arg_count, idx, arg_count)
+                }
+                OpCode::Jump | OpCode::JumpIfFalse => {
+                    let target = self.read_u16_at(pc);
+                    pc += 2;
+                    format!("{:?} {}", opcode, target)
+                }
+                OpCode::StoreLocal | OpCode::LoadLocal => {
+                    let idx = self.read_u16_at(pc);
+                    pc += 2;
+                    format!("{:?} {}", opcode, idx)
+                }
+                OpCode::StoreLocalU8 | OpCode::LoadLocalU8 => {
+                    let idx = self.instructions[pc];
+                    pc += 1;
+                    format!("{:?} {}", opcode, idx)
+                }
+                _ => format!("{:?}", opcode),
+            };
+
+            out.push_str(&format!("{:04} {}\n", offset, line));
+        }
+
+        out
+    }
+
+    /// Validates that every opcode byte in `instructions` decodes to a known
+    /// `OpCode`, that each instruction's operand bytes actually fit within
+    /// the stream, and that every `Jump`/`JumpIfFalse` target lands on an
+    /// opcode boundary rather than mid-operand or past the end. `Compiler`
+    /// only ever emits well-formed bytecode, so the one place any of this
+    /// can go wrong is a corrupted or hand-edited blob passed to
+    /// `from_bytes` — validating it there means `VM::execute`'s hot loop can
+    /// decode straight into the `OpCode` enum and jump on a raw `u16`
+    /// offset without re-checking either on every step.
+    pub(crate) fn validate_opcodes(&self) -> Result<(), String> {
+        let mut boundaries = std::collections::HashSet::new();
+        let mut jumps = Vec::new();
+
+        let mut pc = 0;
+        while pc < self.instructions.len() {
+            boundaries.insert(pc);
+            let byte = self.instructions[pc];
+            let opcode = OpCode::from_u8(byte)
+                .ok_or_else(|| crate::error::ErrorKind::UnknownOpcode(byte).to_string())?;
+            pc += 1;
+
+            let operand_len = match opcode {
+                OpCode::LoadConst
+                | OpCode::LoadVar
+                | OpCode::Jump
+                | OpCode::JumpIfFalse
+                | OpCode::StoreLocal
+                | OpCode::LoadLocal => 2,
+                OpCode::LoadConstU8 | OpCode::LoadVarU8 | OpCode::StoreLocalU8 | OpCode::LoadLocalU8 => 1,
+                OpCode::Call | OpCode::CallUser => 3,
+                OpCode::CallU8 => 2,
+                OpCode::Add
+                | OpCode::Sub
+                | OpCode::Mul
+                | OpCode::Div
+                | OpCode::Pow
+                | OpCode::Neg
+                | OpCode::Lt
+                | OpCode::Gt
+                | OpCode::Le
+                | OpCode::Ge
+                | OpCode::Eq
+                | OpCode::Ne
+                | OpCode::And
+                | OpCode::Or
+                | OpCode::Not
+                | OpCode::Rand
+                | OpCode::Randn
+                | OpCode::Dup
+                | OpCode::Swap
+                | OpCode::Pop => 0,
+            };
+
+            if pc + operand_len > self.instructions.len() {
+                return Err(format!(
+                    "Truncated operand for {:?} at instruction offset {}",
+                    opcode,
+                    pc - 1
+                ));
+            }
+
+            if matches!(opcode, OpCode::Jump | OpCode::JumpIfFalse) {
+                jumps.push((opcode, pc - 1, self.read_u16_at(pc) as usize));
+            }
+
+            pc += operand_len;
         }
+
+        for (opcode, offset, target) in jumps {
+            // Landing exactly on `instructions.len()` is valid too: it's
+            // where the execution loop naturally stops, e.g. a ternary's
+            // "jump past the else branch" when it's also the program's
+            // last instruction.
+            if target != self.instructions.len() && !boundaries.contains(&target) {
+                return Err(format!(
+                    "{:?} at instruction offset {} targets {}, which is not a valid instruction boundary",
+                    opcode, offset, target
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Converts this byte-stream program into a `PackedProgram` — the
+    /// fixed-width `u32`-per-instruction alternative encoding (see
+    /// `packed`'s module doc). User-defined function bodies in `user_funcs`
+    /// are left as ordinary byte-stream `Program`s; only the top-level
+    /// instruction stream is packed.
+    pub fn to_packed(&self) -> Result<crate::packed::PackedProgram, String> {
+        crate::packed::from_program(self)
+    }
+
+    #[inline]
+    pub(crate) fn read_u16_at(&self, pos: usize) -> u16 {
+        let high = self.instructions[pos] as u16;
+        let low = self.instructions[pos + 1] as u16;
+        (high << 8) | low
+    }
+
+    /// Serializes the portable parts of this program (instructions,
+    /// constants, var names, function names and metadata) into a compact
+    /// versioned binary blob. `func_table` is rebuilt from `func_names` on
+    /// load, so custom (non-builtin) functions are not round-tripped.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::new();
+        write_u32(&mut buf, PROGRAM_FORMAT_VERSION);
+        write_bytes_section(&mut buf, &self.instructions);
+        write_f64_section(&mut buf, &self.constants);
+        write_string_section(&mut buf, &self.var_names);
+        write_string_section(&mut buf, &self.func_names);
+        write_metadata_section(&mut buf, &self.func_metadata);
+        buf
+    }
+
+    /// Deserializes a program produced by `to_bytes`, resolving each
+    /// `func_names` entry against the builtin registry to rebuild
+    /// `func_table`. Errors with `ErrorKind::UnknownFunction`'s message
+    /// (without the list of available functions a compile-time error
+    /// appends) if a name is no longer recognized.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Program, String> {
+        let mut pos = 0;
+
+        let version = read_u32(bytes, &mut pos)?;
+        if version != PROGRAM_FORMAT_VERSION {
+            return Err(format!("Unsupported program format version: {}", version));
+        }
+
+        let instructions = read_bytes_section(bytes, &mut pos)?;
+        let constants = read_f64_section(bytes, &mut pos)?;
+        let var_names = read_string_section(bytes, &mut pos)?;
+        let func_names = read_string_section(bytes, &mut pos)?;
+        let func_metadata = read_metadata_section(bytes, &mut pos)?;
+
+        let mut func_table = Vec::with_capacity(func_names.len());
+        for name in &func_names {
+            let func = crate::compiler::resolve_builtin(name)
+                .ok_or_else(|| crate::error::ErrorKind::UnknownFunction(name.clone()).to_string())?;
+            func_table.push(func);
+        }
+
+        let mut program = Program::new();
+        program.instructions = instructions;
+        program.constants = constants;
+        program.var_names = var_names;
+        program.func_names = func_names;
+        program.func_metadata = func_metadata;
+        program.func_table = func_table;
+        program.validate_opcodes()?;
+        Ok(program)
     }
 }
 
+fn write_u32(buf: &mut Vec<u8>, value: u32) {
+    buf.extend_from_slice(&value.to_le_bytes());
+}
+
+fn write_bytes_section(buf: &mut Vec<u8>, data: &[u8]) {
+    write_u32(buf, data.len() as u32);
+    buf.extend_from_slice(data);
+}
+
+fn write_f64_section(buf: &mut Vec<u8>, data: &[f64]) {
+    write_u32(buf, data.len() as u32);
+    for value in data {
+        buf.extend_from_slice(&value.to_le_bytes());
+    }
+}
+
+fn write_string_section(buf: &mut Vec<u8>, data: &[String]) {
+    write_u32(buf, data.len() as u32);
+    for s in data {
+        let bytes = s.as_bytes();
+        write_u32(buf, bytes.len() as u32);
+        buf.extend_from_slice(bytes);
+    }
+}
+
+fn write_metadata_section(buf: &mut Vec<u8>, data: &[FunctionMetadata]) {
+    write_u32(buf, data.len() as u32);
+    for meta in data {
+        match meta.expected_args {
+            Some(n) => {
+                buf.push(1);
+                write_u32(buf, n as u32);
+            }
+            None => buf.push(0),
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], pos: &mut usize) -> Result<u32, String> {
+    if *pos + 4 > bytes.len() {
+        return Err("Unexpected end of program bytes".to_string());
+    }
+    let value = u32::from_le_bytes(bytes[*pos..*pos + 4].try_into().unwrap());
+    *pos += 4;
+    Ok(value)
+}
+
+fn read_bytes_section(bytes: &[u8], pos: &mut usize) -> Result<Vec<u8>, String> {
+    let len = read_u32(bytes, pos)? as usize;
+    if *pos + len > bytes.len() {
+        return Err("Unexpected end of program bytes".to_string());
+    }
+    let data = bytes[*pos..*pos + len].to_vec();
+    *pos += len;
+    Ok(data)
+}
+
+fn read_f64_section(bytes: &[u8], pos: &mut usize) -> Result<Vec<f64>, String> {
+    let len = read_u32(bytes, pos)? as usize;
+    let mut out = Vec::with_capacity(len);
+    for _ in 0..len {
+        if *pos + 8 > bytes.len() {
+            return Err("Unexpected end of program bytes".to_string());
+        }
+        out.push(f64::from_le_bytes(bytes[*pos..*pos + 8].try_into().unwrap()));
+        *pos += 8;
+    }
+    Ok(out)
+}
+
+fn read_string_section(bytes: &[u8], pos: &mut usize) -> Result<Vec<String>, String> {
+    let len = read_u32(bytes, pos)? as usize;
+    let mut out = Vec::with_capacity(len);
+    for _ in 0..len {
+        let str_len = read_u32(bytes, pos)? as usize;
+        if *pos + str_len > bytes.len() {
+            return Err("Unexpected end of program bytes".to_string());
+        }
+        let s = String::from_utf8(bytes[*pos..*pos + str_len].to_vec())
+            .map_err(|e| format!("Invalid UTF-8 in program bytes: {}", e))?;
+        *pos += str_len;
+        out.push(s);
+    }
+    Ok(out)
+}
+
+fn read_metadata_section(bytes: &[u8], pos: &mut usize) -> Result<Vec<FunctionMetadata>, String> {
+    let len = read_u32(bytes, pos)? as usize;
+    let mut out = Vec::with_capacity(len);
+    for _ in 0..len {
+        if *pos >= bytes.len() {
+            return Err("Unexpected end of program bytes".to_string());
+        }
+        let tag = bytes[*pos];
+        *pos += 1;
+        let meta = match tag {
+            0 => FunctionMetadata::new(None),
+            1 => FunctionMetadata::new(Some(read_u32(bytes, pos)? as usize)),
+            _ => return Err(format!("Invalid function metadata tag: {}", tag)),
+        };
+        out.push(meta);
+    }
+    Ok(out)
+}
+
 impl std::fmt::Debug for Program {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Program")
@@ -101,6 +588,18 @@ mod tests {
         assert_eq!(OpCode::LoadConst as u8, 0);
         assert_eq!(OpCode::Add as u8, 2);
         assert_eq!(OpCode::Call as u8, 8);
+        assert_eq!(OpCode::Lt as u8, 9);
+        assert_eq!(OpCode::Not as u8, 17);
+        assert_eq!(OpCode::Jump as u8, 18);
+        assert_eq!(OpCode::JumpIfFalse as u8, 19);
+        assert_eq!(OpCode::CallUser as u8, 20);
+    }
+
+    #[test]
+    fn test_program_default_max_call_depth() {
+        let program = Program::new();
+        assert_eq!(program.max_call_depth, DEFAULT_MAX_CALL_DEPTH);
+        assert_eq!(program.user_funcs.len(), 0);
     }
 
     #[test]
@@ -132,4 +631,193 @@ mod tests {
         assert_eq!(cloned.constants, program.constants);
         assert_eq!(cloned.var_names, program.var_names);
     }
+
+    #[test]
+    fn test_disassemble_load_const_and_var() {
+        let mut program = Program::new();
+        program.constants.push(42.0);
+        program.var_names.push("y".to_string());
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::LoadVar as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+
+        let text = program.disassemble();
+        assert!(text.contains("LoadConst 0 ; 42"));
+        assert!(text.contains("LoadVar 0 ; y"));
+    }
+
+    #[test]
+    fn test_disassemble_call() {
+        let mut program = Program::new();
+        program.constants.push(1.0);
+        program.constants.push(2.0);
+        program.func_names.push("max".to_string());
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 1]);
+        program.instructions.push(OpCode::Call as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(2);
+
+        let text = program.disassemble();
+        assert!(text.contains("Call 0,2 ; max/2"));
+    }
+
+    #[test]
+    fn test_disassemble_unknown_opcode() {
+        let mut program = Program::new();
+        program.instructions.push(255);
+
+        let text = program.disassemble();
+        assert!(text.contains("Unknown opcode"));
+    }
+
+    #[test]
+    fn test_to_bytes_from_bytes_round_trip() {
+        let mut program = Program::new();
+        program.constants.push(1.5);
+        program.constants.push(2.5);
+        program.var_names.push("x".to_string());
+        program.func_names.push("sqrt".to_string());
+        program.func_metadata.push(FunctionMetadata::fixed(1));
+        program.instructions.push(OpCode::LoadVar as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::Call as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(1);
+
+        let bytes = program.to_bytes();
+        let restored = Program::from_bytes(&bytes).unwrap();
+
+        assert_eq!(restored.instructions, program.instructions);
+        assert_eq!(restored.constants, program.constants);
+        assert_eq!(restored.var_names, program.var_names);
+        assert_eq!(restored.func_names, program.func_names);
+        assert_eq!(restored.func_table.len(), 1);
+        assert_eq!(restored.func_table[0](&[4.0]), 2.0);
+    }
+
+    #[test]
+    fn test_from_bytes_unknown_function_errors() {
+        let mut program = Program::new();
+        program.func_names.push("not_a_real_function".to_string());
+        program.func_metadata.push(FunctionMetadata::variadic());
+
+        let bytes = program.to_bytes();
+        let result = Program::from_bytes(&bytes);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Unknown function"));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_bad_version() {
+        let bytes = vec![99, 0, 0, 0];
+        let result = Program::from_bytes(&bytes);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_unknown_opcode_byte() {
+        let mut program = Program::new();
+        program.instructions.push(255);
+
+        let bytes = program.to_bytes();
+        let result = Program::from_bytes(&bytes);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Unknown opcode"));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_truncated_operand() {
+        let mut program = Program::new();
+        program.constants.push(1.0);
+        // LoadConst needs a 2-byte operand, but only one byte follows.
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.push(0);
+
+        let bytes = program.to_bytes();
+        let result = Program::from_bytes(&bytes);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Truncated operand"));
+    }
+
+    #[test]
+    fn test_validate_opcodes_accepts_every_opcode_variant() {
+        let mut program = Program::new();
+        program.constants.push(1.0);
+        program.var_names.push("x".to_string());
+        program.func_names.push("sqrt".to_string());
+        program.func_metadata.push(FunctionMetadata::fixed(1));
+        program.local_count = 1;
+
+        program.instructions.push(OpCode::LoadConstU8 as u8);
+        program.instructions.push(0);
+        program.instructions.push(OpCode::LoadVarU8 as u8);
+        program.instructions.push(0);
+        program.instructions.push(OpCode::CallU8 as u8);
+        program.instructions.push(0);
+        program.instructions.push(1);
+        program.instructions.push(OpCode::StoreLocalU8 as u8);
+        program.instructions.push(0);
+        program.instructions.push(OpCode::LoadLocalU8 as u8);
+        program.instructions.push(0);
+        program.instructions.push(OpCode::Not as u8);
+
+        assert!(program.validate_opcodes().is_ok());
+    }
+
+    #[test]
+    fn test_validate_opcodes_accepts_jump_to_end_of_instructions() {
+        let mut program = Program::new();
+        // JumpIfFalse straight past the end of the (empty) else branch - a
+        // real ternary whose false side is the program's last instruction
+        // patches its jump to exactly `instructions.len()`.
+        program.instructions.push(OpCode::JumpIfFalse as u8);
+        program.instructions.extend_from_slice(&[0, 3]);
+
+        assert!(program.validate_opcodes().is_ok());
+    }
+
+    #[test]
+    fn test_validate_opcodes_rejects_jump_into_an_operand() {
+        let mut program = Program::new();
+        program.constants.push(1.0);
+        // Jump targets offset 1, which is the middle of this LoadConst's
+        // own u16 operand, not an opcode boundary.
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::Jump as u8);
+        program.instructions.extend_from_slice(&[0, 1]);
+
+        let result = program.validate_opcodes();
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("not a valid instruction boundary"));
+    }
+
+    #[test]
+    fn test_validate_opcodes_rejects_jump_past_end_of_instructions() {
+        let mut program = Program::new();
+        program.instructions.push(OpCode::Jump as u8);
+        program.instructions.extend_from_slice(&[0, 99]);
+
+        let result = program.validate_opcodes();
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("not a valid instruction boundary"));
+    }
+
+    #[test]
+    fn test_from_bytes_rejects_a_jump_into_an_operand() {
+        let mut program = Program::new();
+        program.constants.push(1.0);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::Jump as u8);
+        program.instructions.extend_from_slice(&[0, 1]);
+
+        let bytes = program.to_bytes();
+        let result = Program::from_bytes(&bytes);
+        assert!(result.is_err());
+    }
 }
@@ -1,12 +1,46 @@
 use crate::ast::{BinaryOp, Expr, UnaryOp};
-use crate::bytecode::{BuiltinFn, OpCode, Program};
+use crate::bytecode::{BuiltinFn, FunctionMetadata, OpCode, Program};
+use crate::registers::{RegisterAllocator, RegisterOp, RegisterProgram};
 use std::collections::HashMap;
 
+/// Default cap on AST nesting depth during `optimize_expr`/`compile_expr`,
+/// overridable via `Compiler::with_max_depth`. Guards against a
+/// pathologically nested input (`((((...))))`, a long `a+a+a+...` chain)
+/// blowing the native stack instead of failing cleanly.
+const DEFAULT_MAX_DEPTH: u32 = 256;
+
 /// Compiler with constant folding optimization
 pub struct Compiler {
     program: Program,
     var_map: HashMap<String, u16>,
     func_map: HashMap<String, u16>,
+    /// name -> (index into `Program::user_funcs`, arity), pre-registered via
+    /// `with_user_functions` so definitions can call each other (including
+    /// themselves) before their bodies are compiled.
+    user_func_map: HashMap<String, (u16, usize)>,
+    /// name -> (implementation, arity metadata), registered via
+    /// `with_function` so callers can extend the function set without
+    /// forking the crate. Consulted before falling back to the builtin
+    /// registry, so a custom registration can shadow a builtin name.
+    custom_funcs: HashMap<String, (BuiltinFn, FunctionMetadata)>,
+    max_depth: u32,
+    depth: u32,
+    /// Debug-string key (see `compile_multi`) -> local slot, for subexpressions
+    /// hash-consed across `compile_multi`'s outputs. Empty for an ordinary
+    /// single-expression `compile`, so `compile_expr`'s CSE check is a no-op
+    /// `HashMap::is_empty` and costs nothing on that path.
+    cse_slots: HashMap<String, u16>,
+    /// Slots already computed-and-stored this compilation, so the second and
+    /// later occurrence of a hash-consed subtree loads instead of recomputing.
+    cse_emitted: std::collections::HashSet<u16>,
+    /// Mirrors the real VM stack's depth during an ordinary (non-`compile_multi`)
+    /// compile, one entry per stack slot, holding the CSE key of whichever
+    /// node produced that value (`None` if it isn't a repeat-candidate).
+    /// `compile_expr` consults `.last()` to tell whether a repeated
+    /// subexpression's value is *exactly* the current stack top — the only
+    /// time a plain `Dup` can stand in for recompiling it. Always empty
+    /// during `compile_multi`, which hash-conses via `cse_slots` instead.
+    cse_stack_keys: Vec<Option<String>>,
 }
 
 impl Compiler {
@@ -15,22 +49,294 @@ impl Compiler {
             program: Program::new(),
             var_map: HashMap::new(),
             func_map: HashMap::new(),
+            user_func_map: HashMap::new(),
+            custom_funcs: HashMap::new(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            depth: 0,
+            cse_slots: HashMap::new(),
+            cse_emitted: std::collections::HashSet::new(),
+            cse_stack_keys: Vec::new(),
+        }
+    }
+
+    /// Overrides the maximum AST nesting depth `optimize_expr`/`compile_expr`
+    /// will recurse through before aborting with an error (default
+    /// `DEFAULT_MAX_DEPTH`).
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Pre-register user-defined functions (name, arity) so calls to them
+    /// (including recursive/mutually-recursive calls) lower to
+    /// `OpCode::CallUser` rather than the builtin `Call`.
+    pub fn with_user_functions(mut self, funcs: &[(String, usize)]) -> Self {
+        for (idx, (name, arity)) in funcs.iter().enumerate() {
+            self.user_func_map.insert(name.clone(), (idx as u16, *arity));
         }
+        self
     }
 
-    pub fn compile(mut self, expr: Expr) -> Program {
+    /// Registers a custom function under `name`, so that calls to it
+    /// compile to `OpCode::Call` just like a builtin, with `meta`'s arity
+    /// checked against each call site at compile time. Lets callers inject
+    /// domain functions (e.g. a Black-Scholes `cdf`, `clamp`, `lerp`)
+    /// without forking the crate.
+    pub fn with_function(mut self, name: &str, f: BuiltinFn, meta: FunctionMetadata) -> Self {
+        self.register_fn(name, f, meta);
+        self
+    }
+
+    /// In-place counterpart to `with_function`, for callers that hold a
+    /// `&mut Compiler` rather than the owned builder chain (e.g. a host
+    /// embedding binding that registers functions incrementally rather than
+    /// all at once). Overwrites any prior registration under `name`.
+    pub fn register_fn(&mut self, name: &str, f: BuiltinFn, meta: FunctionMetadata) {
+        self.custom_funcs.insert(name.to_string(), (f, meta));
+    }
+
+    pub fn compile(mut self, expr: Expr) -> Result<Program, String> {
         // Optimize AST before compilation
-        let optimized = self.optimize_expr(expr);
-        self.compile_expr(optimized);
-        self.program
+        let optimized = self.optimize_expr(expr)?;
+        self.compile_expr(optimized)?;
+        Ok(self.program)
+    }
+
+    /// Increments the nesting-depth counter, errors if it now exceeds
+    /// `max_depth`, runs `f`, then decrements the counter back down.
+    /// Shared by `optimize_expr` and `compile_expr` so both tree walks are
+    /// bounded the same way.
+    fn with_depth_guard<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            self.depth -= 1;
+            return Err(format!(
+                "Expression nesting depth exceeds the maximum of {} levels",
+                self.max_depth
+            ));
+        }
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Compile a unit made up of zero or more user-defined functions plus a
+    /// main expression. Each definition's body is compiled into its own
+    /// sub-`Program` stored in `Program::user_funcs`; definitions may call
+    /// each other (and themselves) since all signatures are registered
+    /// up front via `with_user_functions`. `self`'s own config (`max_depth`,
+    /// registered custom functions) is carried over to every sub-compiler,
+    /// so a caller that configured `self` before calling this sees that
+    /// config applied uniformly across definitions and the main expression.
+    pub fn compile_program(
+        self,
+        defs: Vec<(String, Vec<String>, Expr)>,
+        main: Expr,
+    ) -> Result<Program, String> {
+        let signatures: Vec<(String, usize)> = defs
+            .iter()
+            .map(|(name, params, _)| (name.clone(), params.len()))
+            .collect();
+
+        let mut user_funcs = Vec::with_capacity(defs.len());
+        let mut user_func_metadata = Vec::with_capacity(defs.len());
+        for (_, params, body) in defs {
+            let mut def_compiler = self.config_clone().with_user_functions(&signatures);
+            for param in &params {
+                def_compiler.resolve_var(param.clone());
+            }
+            user_func_metadata.push(FunctionMetadata::fixed(params.len()));
+            user_funcs.push(def_compiler.compile(body)?);
+        }
+
+        let main_compiler = self.config_clone().with_user_functions(&signatures);
+        let mut program = main_compiler.compile(main)?;
+        program.user_funcs = user_funcs;
+        program.user_func_metadata = user_func_metadata;
+        Ok(program)
+    }
+
+    /// Clones this compiler's standalone config (`max_depth`, registered
+    /// custom functions) into a fresh `Compiler`, for `compile_program` to
+    /// seed each per-definition sub-compiler the same way as the caller's.
+    fn config_clone(&self) -> Self {
+        let mut compiler = Self::new().with_max_depth(self.max_depth);
+        for (name, (f, meta)) in &self.custom_funcs {
+            compiler.register_fn(name, *f, *meta);
+        }
+        compiler
+    }
+
+    /// Compiles several expressions sharing one variable namespace into a
+    /// single program, leaving one result per output on the stack (in order)
+    /// for `VM::run_multi` to collect — e.g. an option pricer's `d1`, `d2`
+    /// and `price` all reference the same `sigma*sqrt(t)`/`exp(-r*t)` terms,
+    /// and a caller may want any subset of them without recomputing shared
+    /// intermediates for each.
+    ///
+    /// Before compiling, hash-conses identical AST subtrees (by structural
+    /// equality, keyed on `Expr`'s `Debug` output) across every output: a
+    /// subtree that recurs gets one `OpCode::StoreLocal` slot, computed once
+    /// at its first occurrence and reloaded with `OpCode::LoadLocal`
+    /// everywhere else. A bare variable or constant is never hash-consed —
+    /// reloading it is already as cheap as recomputing it — and a `rand`/
+    /// `randn` call never is either, since each occurrence must keep drawing
+    /// an independent sample.
+    pub fn compile_multi(mut self, outputs: Vec<Expr>) -> Result<Program, String> {
+        let mut optimized = Vec::with_capacity(outputs.len());
+        for expr in outputs {
+            optimized.push(self.optimize_expr(expr)?);
+        }
+
+        let mut occurrences: HashMap<String, u32> = HashMap::new();
+        for expr in &optimized {
+            count_cse_candidates(expr, &mut occurrences);
+        }
+
+        let mut cse_slots: HashMap<String, u16> = HashMap::new();
+        for expr in &optimized {
+            assign_cse_slots(expr, &occurrences, &mut cse_slots);
+        }
+        self.program.local_count = cse_slots.len();
+        self.cse_slots = cse_slots;
+
+        self.program.output_count = optimized.len();
+        for expr in optimized {
+            self.compile_expr(expr)?;
+        }
+        Ok(self.program)
+    }
+
+    /// Compiles `expr` into a `RegisterProgram`: a linear-scan allocator
+    /// walks the (optimized) tree assigning each subexpression's result a
+    /// register, freeing a child's register as soon as its parent has
+    /// consumed it, and emits a three-address `RegisterOp` per node instead
+    /// of the stack machine's push/pop sequence. Reuses this compiler's
+    /// `var_map`/`func_map`/`custom_funcs` resolution so register programs
+    /// and bytecode programs assign the same variable/function indices.
+    ///
+    /// Rejects ternary `?:`, `&&`/`||`, and user-defined function calls —
+    /// see `RegisterProgram`'s module doc for why.
+    pub fn compile_registers(mut self, expr: Expr) -> Result<RegisterProgram, String> {
+        let optimized = self.optimize_expr(expr)?;
+        let mut allocator = RegisterAllocator::default();
+        let mut ops = Vec::new();
+        let result = self.compile_register_expr(optimized, &mut allocator, &mut ops)?;
+        Ok(RegisterProgram::new(
+            ops,
+            allocator.register_count(),
+            self.program.func_table,
+            self.program.var_names,
+            result,
+        ))
+    }
+
+    /// Per-node compilation for `compile_registers`, returning the register
+    /// holding that node's result.
+    fn compile_register_expr(
+        &mut self,
+        expr: Expr,
+        alloc: &mut RegisterAllocator,
+        ops: &mut Vec<RegisterOp>,
+    ) -> Result<u16, String> {
+        self.with_depth_guard(|this| match expr {
+            Expr::Number(n) => {
+                let dst = alloc.alloc();
+                ops.push(RegisterOp::LoadConst { dst, value: n });
+                Ok(dst)
+            }
+            Expr::Variable(name) => {
+                let var_idx = this.resolve_var(name);
+                let dst = alloc.alloc();
+                ops.push(RegisterOp::LoadVar { dst, var_idx });
+                Ok(dst)
+            }
+            Expr::Binary { op: op @ (BinaryOp::And | BinaryOp::Or), .. } => Err(format!(
+                "the register backend does not support short-circuiting '{}'; it has no \
+                 branching, so compile with Compiler::compile instead",
+                op.symbol()
+            )),
+            Expr::Binary { op, left, right } => {
+                let lhs = this.compile_register_expr(*left, alloc, ops)?;
+                let rhs = this.compile_register_expr(*right, alloc, ops)?;
+                alloc.free(rhs);
+                alloc.free(lhs);
+                let dst = alloc.alloc();
+                ops.push(register_binop(op, dst, lhs, rhs));
+                Ok(dst)
+            }
+            Expr::Unary { op, expr } => {
+                let src = this.compile_register_expr(*expr, alloc, ops)?;
+                alloc.free(src);
+                let dst = alloc.alloc();
+                ops.push(match op {
+                    UnaryOp::Neg => RegisterOp::Neg { dst, src },
+                    UnaryOp::Not => RegisterOp::Not { dst, src },
+                });
+                Ok(dst)
+            }
+            Expr::Call { func, args } => {
+                if this.user_func_map.contains_key(&func) {
+                    return Err(format!(
+                        "the register backend does not support calls to user-defined \
+                         function '{}'; it has no encoding for a recursive call, so \
+                         compile with Compiler::compile_unit instead",
+                        func
+                    ));
+                }
+                if !this.custom_funcs.contains_key(&func) && matches!(func.as_str(), "rand" | "randn") {
+                    if !args.is_empty() {
+                        return Err(format!(
+                            "Function '{}' expects 0 arguments, but got {}",
+                            func,
+                            args.len()
+                        ));
+                    }
+                    let dst = alloc.alloc();
+                    ops.push(if func == "rand" {
+                        RegisterOp::Rand { dst }
+                    } else {
+                        RegisterOp::Randn { dst }
+                    });
+                    return Ok(dst);
+                }
+
+                let arg_count = args.len() as u8;
+                let args_base = alloc.next_free();
+                for arg in args {
+                    this.compile_register_expr(arg, alloc, ops)?;
+                }
+                for i in (0..arg_count).rev() {
+                    alloc.free(args_base + i as u16);
+                }
+                let func_idx = this.resolve_func(func, arg_count as usize)?;
+                let dst = alloc.alloc();
+                ops.push(RegisterOp::Call { dst, func_idx, args_base, arg_count });
+                Ok(dst)
+            }
+            Expr::Cond { .. } => Err(
+                "the register backend does not support ternary '?:' expressions; its \
+                 straight-line registers have no branching, so compile with \
+                 Compiler::compile instead"
+                    .to_string(),
+            ),
+            Expr::Str(_) => Err(
+                "String literals are not yet supported; the VM stack is f64-only".to_string(),
+            ),
+            Expr::OpFunc(op) => Err(format!(
+                "Operator reference '\\{}' is not yet supported as a value; \
+                 there is no higher-order builtin that accepts one",
+                op.symbol()
+            )),
+        })
     }
 
     /// Constant folding optimization
-    fn optimize_expr(&self, expr: Expr) -> Expr {
-        match expr {
+    fn optimize_expr(&mut self, expr: Expr) -> Result<Expr, String> {
+        self.with_depth_guard(|this| match expr {
             Expr::Binary { op, left, right } => {
-                let left = self.optimize_expr(*left);
-                let right = self.optimize_expr(*right);
+                let left = this.optimize_expr(*left)?;
+                let right = this.optimize_expr(*right)?;
 
                 // Fold if both operands are constants
                 if let (Expr::Number(a), Expr::Number(b)) = (&left, &right) {
@@ -41,21 +347,29 @@ impl Compiler {
                         BinaryOp::Div => {
                             if *b == 0.0 {
                                 // Don't fold division by zero
-                                return Expr::Binary {
+                                return Ok(Expr::Binary {
                                     op,
                                     left: Box::new(left),
                                     right: Box::new(right),
-                                };
+                                });
                             }
                             a / b
                         }
                         BinaryOp::Pow => a.powf(*b),
+                        BinaryOp::Lt => bool_to_num(a < b),
+                        BinaryOp::Gt => bool_to_num(a > b),
+                        BinaryOp::Le => bool_to_num(a <= b),
+                        BinaryOp::Ge => bool_to_num(a >= b),
+                        BinaryOp::Eq => bool_to_num(a == b),
+                        BinaryOp::Ne => bool_to_num(a != b),
+                        BinaryOp::And => bool_to_num(*a != 0.0 && *b != 0.0),
+                        BinaryOp::Or => bool_to_num(*a != 0.0 || *b != 0.0),
                     };
-                    return Expr::Number(result);
+                    return Ok(Expr::Number(result));
                 }
 
                 // Algebraic optimizations
-                match (&left, &right, op) {
+                Ok(match (&left, &right, op) {
                     // x + 0 = x, 0 + x = x
                     (Expr::Number(0.0), _, BinaryOp::Add) => right,
                     (_, Expr::Number(0.0), BinaryOp::Add) => left,
@@ -78,30 +392,107 @@ impl Compiler {
                         left: Box::new(left),
                         right: Box::new(right),
                     },
-                }
+                })
             }
             Expr::Unary { op, expr } => {
-                let expr = self.optimize_expr(*expr);
-                if let Expr::Number(n) = expr {
+                let expr = this.optimize_expr(*expr)?;
+                Ok(if let Expr::Number(n) = expr {
                     match op {
                         UnaryOp::Neg => Expr::Number(-n),
+                        UnaryOp::Not => Expr::Number(if n == 0.0 { 1.0 } else { 0.0 }),
                     }
                 } else {
                     Expr::Unary {
                         op,
                         expr: Box::new(expr),
                     }
-                }
+                })
             }
             Expr::Call { func, args } => {
-                let args = args.into_iter().map(|a| self.optimize_expr(a)).collect();
-                Expr::Call { func, args }
+                let args = args
+                    .into_iter()
+                    .map(|a| this.optimize_expr(a))
+                    .collect::<Result<Vec<_>, _>>()?;
+
+                // Fold a call to a builtin (not shadowed by a user-defined or
+                // custom registration, same precedence `resolve_func` uses)
+                // when every argument is a constant and the result is finite
+                // — a non-finite result (e.g. `sqrt(-1)`, `ln(0)`) keeps the
+                // `Call` node so the VM still produces the same NaN/inf.
+                if !this.user_func_map.contains_key(&func) && !this.custom_funcs.contains_key(&func) {
+                    if let Some(f) = resolve_builtin(&func) {
+                        let nums: Option<Vec<f64>> = args
+                            .iter()
+                            .map(|a| if let Expr::Number(n) = a { Some(*n) } else { None })
+                            .collect();
+                        if let Some(nums) = nums {
+                            let result = f(&nums);
+                            if result.is_finite() {
+                                return Ok(Expr::Number(result));
+                            }
+                        }
+                    }
+                }
+
+                Ok(Expr::Call { func, args })
             }
-            _ => expr,
-        }
+            Expr::Cond { test, then, else_ } => Ok(Expr::Cond {
+                test: Box::new(this.optimize_expr(*test)?),
+                then: Box::new(this.optimize_expr(*then)?),
+                else_: Box::new(this.optimize_expr(*else_)?),
+            }),
+            _ => Ok(expr),
+        })
+    }
+
+    fn compile_expr(&mut self, expr: Expr) -> Result<(), String> {
+        self.with_depth_guard(|this| {
+            // Empty for every ordinary (non-`compile_multi`) compile, so this
+            // is a single `HashMap::is_empty` check with no allocation.
+            if !this.cse_slots.is_empty() {
+                if is_cse_candidate(&expr) {
+                    let key = format!("{:?}", expr);
+                    if let Some(&slot) = this.cse_slots.get(&key) {
+                        if this.cse_emitted.contains(&slot) {
+                            this.emit_load_local(slot);
+                            return Ok(());
+                        }
+                        this.compile_expr_uncached(expr)?;
+                        this.emit_store_local(slot);
+                        this.emit_load_local(slot);
+                        this.cse_emitted.insert(slot);
+                        return Ok(());
+                    }
+                }
+                return this.compile_expr_uncached(expr);
+            }
+
+            // Ordinary single-expression compile: `cse_stack_keys` mirrors
+            // the real stack's depth, one entry per value, recording the key
+            // of whichever node produced it (or `None` for a non-candidate).
+            // If a repeated subexpression's key is exactly the current stack
+            // top, its value is already sitting there, so `Dup` reuses it
+            // instead of recompiling the whole subtree; otherwise compile it
+            // normally and record its key for a later occurrence to match.
+            let candidate_key = is_cse_candidate(&expr).then(|| format!("{:?}", expr));
+            if candidate_key.is_some() && this.cse_stack_keys.last() == Some(&candidate_key) {
+                this.emit_dup();
+                this.cse_stack_keys.push(candidate_key);
+                return Ok(());
+            }
+
+            let base = this.cse_stack_keys.len();
+            this.compile_expr_uncached(expr)?;
+            this.cse_stack_keys.truncate(base);
+            this.cse_stack_keys.push(candidate_key);
+            Ok(())
+        })
     }
 
-    fn compile_expr(&mut self, expr: Expr) {
+    /// The per-variant compilation `compile_expr` normally dispatches to.
+    /// Split out so `compile_expr` can wrap it with the CSE store/load
+    /// bookkeeping without duplicating this match.
+    fn compile_expr_uncached(&mut self, expr: Expr) -> Result<(), String> {
         match expr {
             Expr::Number(n) => {
                 let idx = self.add_constant(n);
@@ -111,24 +502,151 @@ impl Compiler {
                 let idx = self.resolve_var(name);
                 self.emit_load_var(idx);
             }
+            Expr::Binary { op: BinaryOp::And, left, right } => {
+                self.compile_short_circuit_and(*left, *right)?;
+            }
+            Expr::Binary { op: BinaryOp::Or, left, right } => {
+                self.compile_short_circuit_or(*left, *right)?;
+            }
             Expr::Binary { op, left, right } => {
-                self.compile_expr(*left);
-                self.compile_expr(*right);
+                self.compile_expr(*left)?;
+                self.compile_expr(*right)?;
                 self.emit_binop(op);
             }
             Expr::Unary { op, expr } => {
-                self.compile_expr(*expr);
+                self.compile_expr(*expr)?;
                 self.emit_unop(op);
             }
             Expr::Call { func, args } => {
-                let arg_count = args.len() as u8;
-                for arg in args {
-                    self.compile_expr(arg);
+                if let Some(&(user_idx, arity)) = self.user_func_map.get(&func) {
+                    if args.len() != arity {
+                        return Err(format!(
+                            "Function '{}' expects {} argument{}, but got {}",
+                            func,
+                            arity,
+                            if arity == 1 { "" } else { "s" },
+                            args.len()
+                        ));
+                    }
+                    let arg_count = args.len() as u8;
+                    for arg in args {
+                        self.compile_expr(arg)?;
+                    }
+                    self.emit_call_user(user_idx, arg_count);
+                } else if !self.custom_funcs.contains_key(&func)
+                    && matches!(func.as_str(), "rand" | "randn")
+                {
+                    // `rand`/`randn` sample fresh on every dispatch, so
+                    // (unlike every other builtin) they can't be a plain
+                    // `fn(&[f64]) -> f64` in `func_table` — there's
+                    // nowhere to carry RNG state through a bare function
+                    // pointer. They compile straight to their own opcode
+                    // instead, as long as nothing has shadowed the name.
+                    if !args.is_empty() {
+                        return Err(format!(
+                            "Function '{}' expects 0 arguments, but got {}",
+                            func,
+                            args.len()
+                        ));
+                    }
+                    let opcode = if func == "rand" { OpCode::Rand } else { OpCode::Randn };
+                    self.program.instructions.push(opcode as u8);
+                } else {
+                    let arg_count = args.len() as u8;
+                    for arg in args {
+                        self.compile_expr(arg)?;
+                    }
+                    let func_idx = self.resolve_func(func, arg_count as usize)?;
+                    self.emit_call(func_idx, arg_count);
                 }
-                let func_idx = self.resolve_func(func);
-                self.emit_call(func_idx, arg_count);
+            }
+            Expr::Cond { test, then, else_ } => {
+                // `JumpIfFalse` pops the test, and only one branch runs, so
+                // neither value may stay visible to `Dup` reuse; a CSE slot
+                // first stored inside a branch isn't stored on the other path.
+                let base = self.cse_stack_keys.len();
+                self.compile_expr(*test)?;
+                self.cse_stack_keys.truncate(base);
+                let emitted = self.cse_emitted.clone();
+                let jump_to_else = self.emit_jump(OpCode::JumpIfFalse);
+                self.compile_expr(*then)?;
+                let jump_to_end = self.emit_jump(OpCode::Jump);
+                self.patch_jump(jump_to_else);
+                self.cse_stack_keys.truncate(base);
+                self.cse_emitted = emitted.clone();
+                self.compile_expr(*else_)?;
+                self.patch_jump(jump_to_end);
+                self.cse_emitted = emitted;
+            }
+            Expr::Str(_) => {
+                // No string slot on the stack yet (it's `Vec<f64>`, same gap
+                // as `Expr::OpFunc` below) — string literals parse so the
+                // grammar is ready once a typed `Value` stack lands.
+                return Err(
+                    "String literals are not yet supported; the VM stack is f64-only".to_string(),
+                );
+            }
+            Expr::OpFunc(op) => {
+                // The bytecode/VM model has no function-value slot to put
+                // this in yet (every stack value is a plain f64), so an
+                // operator reference can't be compiled as a standalone
+                // expression. It parses today so the grammar is ready for
+                // higher-order builtins once such a slot exists.
+                return Err(format!(
+                    "Operator reference '\\{}' is not yet supported as a value; \
+                     there is no higher-order builtin that accepts one",
+                    op.symbol()
+                ));
             }
         }
+        Ok(())
+    }
+
+    /// `a && b`: if `a` is falsy the result is `0.0` without evaluating `b`.
+    fn compile_short_circuit_and(&mut self, left: Expr, right: Expr) -> Result<(), String> {
+        let base = self.cse_stack_keys.len();
+        self.compile_expr(left)?;
+        self.cse_stack_keys.truncate(base);
+        let emitted = self.cse_emitted.clone();
+        let jump_to_false = self.emit_jump(OpCode::JumpIfFalse);
+        self.compile_expr(right)?;
+        self.cse_stack_keys.truncate(base);
+        self.cse_emitted = emitted;
+        let jump_to_false_from_right = self.emit_jump(OpCode::JumpIfFalse);
+        let true_idx = self.add_constant(1.0);
+        self.emit_load_const(true_idx);
+        let jump_to_end = self.emit_jump(OpCode::Jump);
+        self.patch_jump(jump_to_false);
+        self.patch_jump(jump_to_false_from_right);
+        let false_idx = self.add_constant(0.0);
+        self.emit_load_const(false_idx);
+        self.patch_jump(jump_to_end);
+        Ok(())
+    }
+
+    /// `a || b`: if `a` is truthy the result is `1.0` without evaluating `b`.
+    fn compile_short_circuit_or(&mut self, left: Expr, right: Expr) -> Result<(), String> {
+        let base = self.cse_stack_keys.len();
+        self.compile_expr(left)?;
+        self.cse_stack_keys.truncate(base);
+        let emitted = self.cse_emitted.clone();
+        let jump_to_check_right = self.emit_jump(OpCode::JumpIfFalse);
+        let true_idx = self.add_constant(1.0);
+        self.emit_load_const(true_idx);
+        let jump_to_end_from_left = self.emit_jump(OpCode::Jump);
+        self.patch_jump(jump_to_check_right);
+        self.compile_expr(right)?;
+        self.cse_stack_keys.truncate(base);
+        self.cse_emitted = emitted;
+        let jump_to_false = self.emit_jump(OpCode::JumpIfFalse);
+        self.emit_load_const(true_idx);
+        let jump_to_end_from_right = self.emit_jump(OpCode::Jump);
+        self.patch_jump(jump_to_false);
+        let false_idx = self.add_constant(0.0);
+        self.emit_load_const(false_idx);
+        self.patch_jump(jump_to_end_from_left);
+        self.patch_jump(jump_to_end_from_right);
+        Ok(())
     }
 
     fn add_constant(&mut self, value: f64) -> u16 {
@@ -151,47 +669,76 @@ impl Compiler {
         idx
     }
 
-    fn resolve_func(&mut self, name: String) -> u16 {
+    /// Resolves a call to `name` with `arg_count` arguments to an index into
+    /// `func_table`, checking arity against the function's `FunctionMetadata`
+    /// (custom registrations are consulted before builtins, so a custom
+    /// function can shadow a builtin of the same name). Errors with
+    /// `UnknownFunction`'s message, enumerating the live registry, if `name`
+    /// isn't registered anywhere.
+    fn resolve_func(&mut self, name: String, arg_count: usize) -> Result<u16, String> {
         if let Some(&idx) = self.func_map.get(&name) {
-            return idx;
+            return Ok(idx);
         }
-        let idx = self.program.func_names.len() as u16;
-        
-        // Register built-in function
-        let func_ptr = match name.as_str() {
-            "sin" => builtin_sin as BuiltinFn,
-            "cos" => builtin_cos as BuiltinFn,
-            "tan" => builtin_tan as BuiltinFn,
-            "sqrt" => builtin_sqrt as BuiltinFn,
-            "abs" => builtin_abs as BuiltinFn,
-            "floor" => builtin_floor as BuiltinFn,
-            "ceil" => builtin_ceil as BuiltinFn,
-            "round" => builtin_round as BuiltinFn,
-            "exp" => builtin_exp as BuiltinFn,
-            "ln" => builtin_ln as BuiltinFn,
-            "log10" => builtin_log10 as BuiltinFn,
-            "max" => builtin_max as BuiltinFn,
-            "min" => builtin_min as BuiltinFn,
-            _ => {
-                // Unknown function - will error at runtime
-                builtin_unknown as BuiltinFn
-            }
+
+        let (func_ptr, metadata) = if let Some(&(f, meta)) = self.custom_funcs.get(&name) {
+            (f, meta)
+        } else if let Some(f) = resolve_builtin(&name) {
+            (f, builtin_metadata(&name).unwrap_or_else(FunctionMetadata::variadic))
+        } else {
+            return Err(format!(
+                "Unknown function: '{}'. Available functions: {}",
+                name,
+                self.available_function_names().join(", ")
+            ));
         };
-        
+
+        if let Some(expected) = metadata.expected_args {
+            if expected != arg_count {
+                return Err(format!(
+                    "Function '{}' expects {} argument{}, but got {}",
+                    name,
+                    expected,
+                    if expected == 1 { "" } else { "s" },
+                    arg_count
+                ));
+            }
+        }
+
+        let idx = self.program.func_names.len() as u16;
         self.program.func_table.push(func_ptr);
         self.program.func_names.push(name.clone());
+        self.program.func_metadata.push(metadata);
         self.func_map.insert(name, idx);
-        idx
+        Ok(idx)
+    }
+
+    /// Names available for calling, builtins plus any custom registrations,
+    /// sorted for a stable `UnknownFunction` hint.
+    fn available_function_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_NAMES.iter().map(|s| s.to_string()).collect();
+        names.extend(self.custom_funcs.keys().cloned());
+        names.sort();
+        names
     }
 
     fn emit_load_const(&mut self, idx: u16) {
-        self.program.instructions.push(OpCode::LoadConst as u8);
-        self.emit_u16(idx);
+        if let Ok(idx8) = u8::try_from(idx) {
+            self.program.instructions.push(OpCode::LoadConstU8 as u8);
+            self.program.instructions.push(idx8);
+        } else {
+            self.program.instructions.push(OpCode::LoadConst as u8);
+            self.emit_u16(idx);
+        }
     }
 
     fn emit_load_var(&mut self, idx: u16) {
-        self.program.instructions.push(OpCode::LoadVar as u8);
-        self.emit_u16(idx);
+        if let Ok(idx8) = u8::try_from(idx) {
+            self.program.instructions.push(OpCode::LoadVarU8 as u8);
+            self.program.instructions.push(idx8);
+        } else {
+            self.program.instructions.push(OpCode::LoadVar as u8);
+            self.emit_u16(idx);
+        }
     }
 
     fn emit_binop(&mut self, op: BinaryOp) {
@@ -201,6 +748,15 @@ impl Compiler {
             BinaryOp::Mul => OpCode::Mul,
             BinaryOp::Div => OpCode::Div,
             BinaryOp::Pow => OpCode::Pow,
+            BinaryOp::Lt => OpCode::Lt,
+            BinaryOp::Gt => OpCode::Gt,
+            BinaryOp::Le => OpCode::Le,
+            BinaryOp::Ge => OpCode::Ge,
+            BinaryOp::Eq => OpCode::Eq,
+            BinaryOp::Ne => OpCode::Ne,
+            // And/Or are compiled via short-circuit jumps in `compile_expr` and never reach here.
+            BinaryOp::And => OpCode::And,
+            BinaryOp::Or => OpCode::Or,
         };
         self.program.instructions.push(opcode as u8);
     }
@@ -208,22 +764,210 @@ impl Compiler {
     fn emit_unop(&mut self, op: UnaryOp) {
         let opcode = match op {
             UnaryOp::Neg => OpCode::Neg,
+            UnaryOp::Not => OpCode::Not,
         };
         self.program.instructions.push(opcode as u8);
     }
 
     fn emit_call(&mut self, func_idx: u16, arg_count: u8) {
-        self.program.instructions.push(OpCode::Call as u8);
-        self.emit_u16(func_idx);
+        if let Ok(idx8) = u8::try_from(func_idx) {
+            self.program.instructions.push(OpCode::CallU8 as u8);
+            self.program.instructions.push(idx8);
+        } else {
+            self.program.instructions.push(OpCode::Call as u8);
+            self.emit_u16(func_idx);
+        }
+        self.program.instructions.push(arg_count);
+    }
+
+    fn emit_call_user(&mut self, user_func_idx: u16, arg_count: u8) {
+        self.program.instructions.push(OpCode::CallUser as u8);
+        self.emit_u16(user_func_idx);
         self.program.instructions.push(arg_count);
     }
 
+    /// Emits a jump opcode with a placeholder `u16` target and returns the
+    /// index of that operand so it can be back-patched once the target
+    /// address is known.
+    fn emit_jump(&mut self, opcode: OpCode) -> usize {
+        self.program.instructions.push(opcode as u8);
+        let operand_pos = self.program.instructions.len();
+        self.emit_u16(0);
+        operand_pos
+    }
+
+    /// Back-patches a previously emitted jump's operand to target the
+    /// current (absolute) end of the instruction stream.
+    fn patch_jump(&mut self, operand_pos: usize) {
+        let target = self.program.instructions.len() as u16;
+        self.program.instructions[operand_pos] = (target >> 8) as u8;
+        self.program.instructions[operand_pos + 1] = (target & 0xFF) as u8;
+    }
+
+    /// Emits `StoreLocal`/`StoreLocalU8` (pops the top of stack into `idx`),
+    /// used by `compile_multi`'s CSE bookkeeping.
+    fn emit_store_local(&mut self, idx: u16) {
+        if let Ok(idx8) = u8::try_from(idx) {
+            self.program.instructions.push(OpCode::StoreLocalU8 as u8);
+            self.program.instructions.push(idx8);
+        } else {
+            self.program.instructions.push(OpCode::StoreLocal as u8);
+            self.emit_u16(idx);
+        }
+    }
+
+    /// Emits `LoadLocal`/`LoadLocalU8` (pushes a copy of slot `idx`), used by
+    /// `compile_multi`'s CSE bookkeeping.
+    fn emit_load_local(&mut self, idx: u16) {
+        if let Ok(idx8) = u8::try_from(idx) {
+            self.program.instructions.push(OpCode::LoadLocalU8 as u8);
+            self.program.instructions.push(idx8);
+        } else {
+            self.program.instructions.push(OpCode::LoadLocal as u8);
+            self.emit_u16(idx);
+        }
+    }
+
+    /// Emits `Dup` (pushes a copy of the top of stack), used by
+    /// `compile_expr`'s single-expression Dup-based CSE.
+    fn emit_dup(&mut self) {
+        self.program.instructions.push(OpCode::Dup as u8);
+    }
+
     fn emit_u16(&mut self, value: u16) {
         self.program.instructions.push((value >> 8) as u8);
         self.program.instructions.push((value & 0xFF) as u8);
     }
 }
 
+fn bool_to_num(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
+
+/// Maps a (non-`And`/`Or`, already checked by the caller) `BinaryOp` to its
+/// `RegisterOp` form.
+fn register_binop(op: BinaryOp, dst: u16, lhs: u16, rhs: u16) -> RegisterOp {
+    match op {
+        BinaryOp::Add => RegisterOp::Add { dst, lhs, rhs },
+        BinaryOp::Sub => RegisterOp::Sub { dst, lhs, rhs },
+        BinaryOp::Mul => RegisterOp::Mul { dst, lhs, rhs },
+        BinaryOp::Div => RegisterOp::Div { dst, lhs, rhs },
+        BinaryOp::Pow => RegisterOp::Pow { dst, lhs, rhs },
+        BinaryOp::Lt => RegisterOp::Lt { dst, lhs, rhs },
+        BinaryOp::Gt => RegisterOp::Gt { dst, lhs, rhs },
+        BinaryOp::Le => RegisterOp::Le { dst, lhs, rhs },
+        BinaryOp::Ge => RegisterOp::Ge { dst, lhs, rhs },
+        BinaryOp::Eq => RegisterOp::Eq { dst, lhs, rhs },
+        BinaryOp::Ne => RegisterOp::Ne { dst, lhs, rhs },
+        BinaryOp::And | BinaryOp::Or => {
+            unreachable!("And/Or are rejected in compile_register_expr before reaching here")
+        }
+    }
+}
+
+/// Whether `expr` is worth hash-consing in `Compiler::compile_multi`. A bare
+/// number/variable is already as cheap to reload as it is to recompile, and
+/// anything with a `rand`/`randn` call *anywhere in its subtree* must keep
+/// drawing an independent sample at every occurrence rather than being
+/// collapsed to one shared value — checked recursively via `Expr::walk`, not
+/// just at the root, since a `rand()` buried inside a `Binary`/`Cond` (e.g.
+/// `cond ? rand() : 0`) is just as non-poolable as a bare one.
+fn is_cse_candidate(expr: &Expr) -> bool {
+    match expr {
+        Expr::Number(_) | Expr::Variable(_) | Expr::Str(_) | Expr::OpFunc(_) => false,
+        Expr::Call { .. } | Expr::Binary { .. } | Expr::Unary { .. } | Expr::Cond { .. } => {
+            !contains_rand_or_randn(expr)
+        }
+    }
+}
+
+/// Whether `expr`'s subtree (including `expr` itself) contains a call to
+/// `rand` or `randn`, used by `is_cse_candidate` to veto memoizing anything
+/// that draws a non-deterministic sample.
+fn contains_rand_or_randn(expr: &Expr) -> bool {
+    let mut found = false;
+    expr.walk(&mut |node| {
+        if let Expr::Call { func, .. } = node {
+            if func == "rand" || func == "randn" {
+                found = true;
+            }
+        }
+        !found
+    });
+    found
+}
+
+/// Counts how many times each CSE-candidate subtree (keyed by its `Debug`
+/// representation) appears across `expr`, for `compile_multi` to call once
+/// per output and accumulate into a single shared map.
+fn count_cse_candidates(expr: &Expr, counts: &mut HashMap<String, u32>) {
+    expr.walk(&mut |node| {
+        if is_cse_candidate(node) {
+            *counts.entry(format!("{:?}", node)).or_insert(0) += 1;
+        }
+        true
+    });
+}
+
+/// Assigns a local slot (in first-occurrence order, across all outputs) to
+/// every subtree that `counts` says recurs more than once.
+fn assign_cse_slots(expr: &Expr, counts: &HashMap<String, u32>, slots: &mut HashMap<String, u16>) {
+    expr.walk(&mut |node| {
+        if is_cse_candidate(node) {
+            let key = format!("{:?}", node);
+            if counts.get(&key).copied().unwrap_or(0) > 1 && !slots.contains_key(&key) {
+                let idx = slots.len() as u16;
+                slots.insert(key, idx);
+            }
+        }
+        true
+    });
+}
+
+/// Names of every builtin, used to enumerate the registry in the
+/// `UnknownFunction` hint.
+const BUILTIN_NAMES: &[&str] = &[
+    "sin", "cos", "tan", "sqrt", "abs", "floor", "ceil", "round", "exp", "ln", "log10", "max",
+    "min", "erf", "erfc", "norm_cdf", "norm_pdf", "rand", "randn",
+];
+
+/// Resolves a builtin function name to its implementation, or `None` if it
+/// isn't a known builtin. Shared with `Program::from_bytes`, which needs to
+/// rebuild `func_table` from `func_names` after deserializing.
+pub(crate) fn resolve_builtin(name: &str) -> Option<BuiltinFn> {
+    let func: BuiltinFn = match name {
+        "sin" => builtin_sin,
+        "cos" => builtin_cos,
+        "tan" => builtin_tan,
+        "sqrt" => builtin_sqrt,
+        "abs" => builtin_abs,
+        "floor" => builtin_floor,
+        "ceil" => builtin_ceil,
+        "round" => builtin_round,
+        "exp" => builtin_exp,
+        "ln" => builtin_ln,
+        "log10" => builtin_log10,
+        "max" => builtin_max,
+        "min" => builtin_min,
+        "erf" => builtin_erf,
+        "erfc" => builtin_erfc,
+        "norm_cdf" => builtin_norm_cdf,
+        "norm_pdf" => builtin_norm_pdf,
+        _ => return None,
+    };
+    Some(func)
+}
+
+/// Arity metadata for a builtin, used to validate calls at compile time.
+fn builtin_metadata(name: &str) -> Option<FunctionMetadata> {
+    match name {
+        "max" | "min" => Some(FunctionMetadata::variadic()),
+        "sin" | "cos" | "tan" | "sqrt" | "abs" | "floor" | "ceil" | "round" | "exp" | "ln"
+        | "log10" | "erf" | "erfc" | "norm_cdf" | "norm_pdf" => Some(FunctionMetadata::fixed(1)),
+        _ => None,
+    }
+}
+
 // Built-in function implementations
 fn builtin_sin(args: &[f64]) -> f64 { args[0].sin() }
 fn builtin_cos(args: &[f64]) -> f64 { args[0].cos() }
@@ -242,8 +986,43 @@ fn builtin_max(args: &[f64]) -> f64 {
 fn builtin_min(args: &[f64]) -> f64 {
     args.iter().fold(f64::INFINITY, |a, &b| a.min(b))
 }
-fn builtin_unknown(_args: &[f64]) -> f64 {
-    f64::NAN
+
+/// The Gauss error function, via the Abramowitz-Stegun 7.1.26 rational
+/// approximation (max error ~1.5e-7), extended to negative `x` using
+/// `erf`'s oddness (`erf(-x) = -erf(x)`).
+fn erf(x: f64) -> f64 {
+    const A1: f64 = 0.254829592;
+    const A2: f64 = -0.284496736;
+    const A3: f64 = 1.421413741;
+    const A4: f64 = -1.453152027;
+    const A5: f64 = 1.061405429;
+    const P: f64 = 0.3275911;
+
+    let sign = if x < 0.0 { -1.0 } else { 1.0 };
+    let x = x.abs();
+    let t = 1.0 / (1.0 + P * x);
+    let poly = ((((A5 * t + A4) * t + A3) * t + A2) * t + A1) * t;
+    sign * (1.0 - poly * (-x * x).exp())
+}
+
+fn builtin_erf(args: &[f64]) -> f64 {
+    erf(args[0])
+}
+fn builtin_erfc(args: &[f64]) -> f64 {
+    1.0 - erf(args[0])
+}
+
+/// The standard normal CDF, `norm_cdf(x) = 0.5 * (1 + erf(x / sqrt(2)))` —
+/// the core primitive behind closed-form Black-Scholes/Black76 pricing.
+fn builtin_norm_cdf(args: &[f64]) -> f64 {
+    0.5 * (1.0 + erf(args[0] / std::f64::consts::SQRT_2))
+}
+
+/// The standard normal PDF, `norm_pdf(x) = e^(-x^2/2) / sqrt(2*pi)`. Also
+/// `norm_cdf`'s own derivative, so `vm::run_grad` reuses this directly
+/// rather than duplicating the formula.
+pub(crate) fn builtin_norm_pdf(args: &[f64]) -> f64 {
+    (-args[0] * args[0] / 2.0).exp() / (2.0 * std::f64::consts::PI).sqrt()
 }
 
 #[cfg(test)]
@@ -251,17 +1030,18 @@ mod tests {
     use super::*;
     use crate::lexer::Lexer;
     use crate::parser::Parser;
+    use crate::vm::Context;
 
     fn compile_expr(input: &str) -> Program {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer).unwrap();
         let ast = parser.parse().unwrap();
-        Compiler::new().compile(ast)
+        Compiler::new().compile(ast).unwrap()
     }
 
     #[test]
     fn test_constant_folding_arithmetic() {
-        let compiler = Compiler::new();
+        let mut compiler = Compiler::new();
         
         // 2 + 3 should fold to 5
         let expr = Expr::Binary {
@@ -269,13 +1049,13 @@ mod tests {
             left: Box::new(Expr::Number(2.0)),
             right: Box::new(Expr::Number(3.0)),
         };
-        let optimized = compiler.optimize_expr(expr);
+        let optimized = compiler.optimize_expr(expr).unwrap();
         assert_eq!(optimized, Expr::Number(5.0));
     }
 
     #[test]
     fn test_constant_folding_multiply_zero() {
-        let compiler = Compiler::new();
+        let mut compiler = Compiler::new();
         
         // x * 0 should fold to 0
         let expr = Expr::Binary {
@@ -283,13 +1063,13 @@ mod tests {
             left: Box::new(Expr::Variable("x".to_string())),
             right: Box::new(Expr::Number(0.0)),
         };
-        let optimized = compiler.optimize_expr(expr);
+        let optimized = compiler.optimize_expr(expr).unwrap();
         assert_eq!(optimized, Expr::Number(0.0));
     }
 
     #[test]
     fn test_constant_folding_multiply_one() {
-        let compiler = Compiler::new();
+        let mut compiler = Compiler::new();
         
         // x * 1 should fold to x
         let expr = Expr::Binary {
@@ -297,13 +1077,13 @@ mod tests {
             left: Box::new(Expr::Variable("x".to_string())),
             right: Box::new(Expr::Number(1.0)),
         };
-        let optimized = compiler.optimize_expr(expr);
+        let optimized = compiler.optimize_expr(expr).unwrap();
         assert_eq!(optimized, Expr::Variable("x".to_string()));
     }
 
     #[test]
     fn test_constant_folding_power() {
-        let compiler = Compiler::new();
+        let mut compiler = Compiler::new();
         
         // 2 ^ 3 should fold to 8
         let expr = Expr::Binary {
@@ -311,23 +1091,118 @@ mod tests {
             left: Box::new(Expr::Number(2.0)),
             right: Box::new(Expr::Number(3.0)),
         };
-        let optimized = compiler.optimize_expr(expr);
+        let optimized = compiler.optimize_expr(expr).unwrap();
         assert_eq!(optimized, Expr::Number(8.0));
     }
 
     #[test]
     fn test_constant_folding_negation() {
-        let compiler = Compiler::new();
+        let mut compiler = Compiler::new();
         
         // -5 should fold to -5
         let expr = Expr::Unary {
             op: UnaryOp::Neg,
             expr: Box::new(Expr::Number(5.0)),
         };
-        let optimized = compiler.optimize_expr(expr);
+        let optimized = compiler.optimize_expr(expr).unwrap();
         assert_eq!(optimized, Expr::Number(-5.0));
     }
 
+    #[test]
+    fn test_constant_folding_builtin_call() {
+        let mut compiler = Compiler::new();
+
+        // sqrt(4) should fold to 2
+        let expr = Expr::Call { func: "sqrt".to_string(), args: vec![Expr::Number(4.0)] };
+        let optimized = compiler.optimize_expr(expr).unwrap();
+        assert_eq!(optimized, Expr::Number(2.0));
+    }
+
+    #[test]
+    fn test_norm_cdf_and_norm_pdf_known_values() {
+        // Abramowitz-Stegun 7.1.26 has a max error of ~1.5e-7, so compare
+        // against the known closed-form values with that tolerance.
+        let program = compile_expr("norm_cdf(0)");
+        assert!((program.eval(&Context::new()).unwrap() - 0.5).abs() < 1e-6);
+
+        let program = compile_expr("norm_pdf(0)");
+        let result = program.eval(&Context::new()).unwrap();
+        assert!((result - 1.0 / (2.0 * std::f64::consts::PI).sqrt()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_erf_and_erfc_are_complementary() {
+        let program = compile_expr("erf(1) + erfc(1)");
+        let result = program.eval(&Context::new()).unwrap();
+        assert!((result - 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_erf_is_odd() {
+        let program = compile_expr("erf(-1.5) + erf(1.5)");
+        let result = program.eval(&Context::new()).unwrap();
+        assert!(result.abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_constant_folding_norm_cdf() {
+        let mut compiler = Compiler::new();
+        let expr = Expr::Call { func: "norm_cdf".to_string(), args: vec![Expr::Number(0.0)] };
+        let optimized = compiler.optimize_expr(expr).unwrap();
+        match optimized {
+            Expr::Number(n) => assert!((n - 0.5).abs() < 1e-6),
+            other => panic!("Expected a folded Expr::Number, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_constant_folding_builtin_call_variadic() {
+        let mut compiler = Compiler::new();
+
+        // max(1, 5, 3) should fold to 5
+        let expr = Expr::Call {
+            func: "max".to_string(),
+            args: vec![Expr::Number(1.0), Expr::Number(5.0), Expr::Number(3.0)],
+        };
+        let optimized = compiler.optimize_expr(expr).unwrap();
+        assert_eq!(optimized, Expr::Number(5.0));
+    }
+
+    #[test]
+    fn test_constant_folding_skips_non_finite_builtin_result() {
+        let mut compiler = Compiler::new();
+
+        // sqrt(-1) is NaN: keep the Call node so the VM reproduces it at runtime.
+        let expr = Expr::Call { func: "sqrt".to_string(), args: vec![Expr::Number(-1.0)] };
+        let optimized = compiler.optimize_expr(expr).unwrap();
+        assert!(matches!(optimized, Expr::Call { .. }));
+    }
+
+    #[test]
+    fn test_constant_folding_skips_non_constant_args() {
+        let mut compiler = Compiler::new();
+
+        // sqrt(x) can't be folded since x isn't a constant.
+        let expr = Expr::Call { func: "sqrt".to_string(), args: vec![Expr::Variable("x".to_string())] };
+        let optimized = compiler.optimize_expr(expr).unwrap();
+        assert!(matches!(optimized, Expr::Call { .. }));
+    }
+
+    #[test]
+    fn test_constant_folding_skips_shadowed_builtin() {
+        // A custom registration named "sqrt" should prevent the built-in
+        // fold, matching resolve_func's shadowing precedence.
+        fn custom_sqrt(args: &[f64]) -> f64 {
+            args[0] * 2.0
+        }
+        let mut compiler =
+            Compiler::new().with_function("sqrt", custom_sqrt, FunctionMetadata::fixed(1));
+
+        let expr = Expr::Call { func: "sqrt".to_string(), args: vec![Expr::Number(4.0)] };
+        let optimized = compiler.optimize_expr(expr).unwrap();
+        assert!(matches!(optimized, Expr::Call { .. }));
+    }
+
     #[test]
     fn test_constant_reuse() {
         // Use a case where constants won't be folded
@@ -352,6 +1227,129 @@ mod tests {
         assert_eq!(program.func_table.len(), 2);
     }
 
+    fn nested_negation(depth: usize) -> Expr {
+        let mut expr = Expr::Number(1.0);
+        for _ in 0..depth {
+            expr = Expr::Unary { op: UnaryOp::Neg, expr: Box::new(expr) };
+        }
+        expr
+    }
+
+    #[test]
+    fn test_compile_rejects_nesting_past_default_max_depth() {
+        // Comfortably past the default 256-level cap.
+        let result = Compiler::new().compile(nested_negation(300));
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("nesting depth"));
+    }
+
+    #[test]
+    fn test_compile_with_max_depth_override() {
+        let expr = nested_negation(10);
+
+        // 10 levels compiles fine under the default cap...
+        assert!(Compiler::new().compile(expr.clone()).is_ok());
+
+        // ...but is rejected once max_depth is tightened below it.
+        let result = Compiler::new().with_max_depth(5).compile(expr);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("nesting depth"));
+    }
+
+    fn builtin_clamp(args: &[f64]) -> f64 {
+        args[0].max(args[1]).min(args[2])
+    }
+
+    #[test]
+    fn test_custom_function_registration() {
+        let lexer = Lexer::new("clamp(x, 0, 1)");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let program = Compiler::new()
+            .with_function("clamp", builtin_clamp, FunctionMetadata::fixed(3))
+            .compile(ast)
+            .unwrap();
+
+        assert_eq!(program.func_names, vec!["clamp".to_string()]);
+        assert_eq!(program.func_table[0](&[5.0, 0.0, 1.0]), 1.0);
+    }
+
+    #[test]
+    fn test_custom_function_arity_mismatch() {
+        let lexer = Lexer::new("clamp(x, 0)");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let result = Compiler::new()
+            .with_function("clamp", builtin_clamp, FunctionMetadata::fixed(3))
+            .compile(ast);
+
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("expects 3 arguments"));
+    }
+
+    #[test]
+    fn test_register_fn_in_place() {
+        let lexer = Lexer::new("clamp(x, 0, 1)");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let mut compiler = Compiler::new();
+        compiler.register_fn("clamp", builtin_clamp, FunctionMetadata::fixed(3));
+        let program = compiler.compile(ast).unwrap();
+
+        assert_eq!(program.func_names, vec!["clamp".to_string()]);
+        assert_eq!(program.func_table[0](&[5.0, 0.0, 1.0]), 1.0);
+    }
+
+    #[test]
+    fn test_unknown_function_is_a_compile_error_not_silent_nan() {
+        let lexer = Lexer::new("frobnicate(x)");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let result = Compiler::new().compile(ast);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Unknown function: 'frobnicate'"));
+    }
+
+    #[test]
+    fn test_string_literal_has_no_compiled_representation_yet() {
+        let lexer = Lexer::new("\"hi\"");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let result = Compiler::new().compile(ast);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("String literals are not yet supported"));
+    }
+
+    #[test]
+    fn test_operator_reference_has_no_compiled_representation_yet() {
+        let lexer = Lexer::new("\\+");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let result = Compiler::new().compile(ast);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Operator reference '\\+'"));
+    }
+
+    #[test]
+    fn test_unknown_function_errors_with_registry_hint() {
+        let lexer = Lexer::new("frobnicate(x)");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let result = Compiler::new().compile(ast);
+        assert!(result.is_err());
+        let err = result.unwrap_err();
+        assert!(err.contains("Unknown function: 'frobnicate'"));
+        assert!(err.contains("sin"));
+        assert!(err.contains("max"));
+    }
+
     #[test]
     fn test_builtin_functions() {
         assert_eq!(builtin_sin(&[0.0]), 0.0);
@@ -365,33 +1363,306 @@ mod tests {
     #[test]
     fn test_compact_bytecode_generation() {
         let program = compile_expr("2 + 3");
-        // Should generate compact bytecode
+        // Should generate compact bytecode, folded to a single constant load
+        // using the 1-byte index form (there's only one constant).
         assert!(program.instructions.len() > 0);
-        assert_eq!(program.instructions[0], OpCode::LoadConst as u8);
+        assert_eq!(program.instructions[0], OpCode::LoadConstU8 as u8);
+    }
+
+    #[test]
+    fn test_load_const_and_load_var_use_short_form_under_256() {
+        let program = compile_expr("x + 1.0");
+        assert_eq!(program.instructions[0], OpCode::LoadVarU8 as u8);
+        let text = program.disassemble();
+        assert!(text.contains("LoadConstU8"));
+        assert!(!text.contains("LoadConst 0") && !text.contains("LoadConst 1"));
+    }
+
+    fn compile_with_max_depth(input: &str, max_depth: u32) -> Program {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+        Compiler::new().with_max_depth(max_depth).compile(ast).unwrap()
+    }
+
+    #[test]
+    fn test_load_const_falls_back_to_wide_form_past_256_constants() {
+        // 300 distinct `x * n` terms, none foldable (one operand is a
+        // variable), so the constant pool grows past the 1-byte index range.
+        // The deep left-associative `+` chain also needs a raised depth cap.
+        let terms: Vec<String> = (0..300).map(|n| format!("x * {}.0", n + 2)).collect();
+        let program = compile_with_max_depth(&terms.join(" + "), 1000);
+
+        assert!(program.constants.len() > 256);
+        assert!(program.instructions.contains(&(OpCode::LoadConstU8 as u8)));
+        assert!(program.instructions.contains(&(OpCode::LoadConst as u8)));
+        assert!(program.disassemble().contains("LoadConst 256 "));
+    }
+
+    #[test]
+    fn test_load_var_falls_back_to_wide_form_past_256_variables() {
+        let names: Vec<String> = (0..300).map(|n| format!("x{}", n)).collect();
+        let program = compile_with_max_depth(&names.join(" + "), 1000);
+
+        assert!(program.var_names.len() > 256);
+        assert!(program.instructions.contains(&(OpCode::LoadVarU8 as u8)));
+        assert!(program.instructions.contains(&(OpCode::LoadVar as u8)));
+        assert!(program.disassemble().contains("LoadVar 256 "));
     }
 
     #[test]
     fn test_algebraic_optimization_add_zero() {
-        let compiler = Compiler::new();
+        let mut compiler = Compiler::new();
         let expr = Expr::Binary {
             op: BinaryOp::Add,
             left: Box::new(Expr::Variable("x".to_string())),
             right: Box::new(Expr::Number(0.0)),
         };
-        let optimized = compiler.optimize_expr(expr);
+        let optimized = compiler.optimize_expr(expr).unwrap();
         assert_eq!(optimized, Expr::Variable("x".to_string()));
     }
 
+    #[test]
+    fn test_compile_comparison() {
+        let program = compile_expr("x > 0");
+        assert!(program.instructions.contains(&(OpCode::Gt as u8)));
+    }
+
+    #[test]
+    fn test_compile_ternary_has_jumps() {
+        let program = compile_expr("x > 0 ? 1 : 2");
+        assert!(program.instructions.contains(&(OpCode::JumpIfFalse as u8)));
+        assert!(program.instructions.contains(&(OpCode::Jump as u8)));
+    }
+
+    #[test]
+    fn test_compile_and_short_circuits_with_jumps() {
+        let program = compile_expr("x && y");
+        // Should use jumps rather than emitting an `And` opcode. Checked via
+        // the disassembly (not a raw byte scan) since a jump target or
+        // operand byte can coincidentally equal `OpCode::And`'s value.
+        assert!(program.instructions.contains(&(OpCode::JumpIfFalse as u8)));
+        assert!(!program.disassemble().contains("And"));
+    }
+
+    #[test]
+    fn test_compile_program_user_function_call() {
+        let lexer = Lexer::new("square(3) + 1");
+        let mut parser = Parser::new(lexer).unwrap();
+        let main = parser.parse().unwrap();
+
+        let body_lexer = Lexer::new("x ^ 2");
+        let mut body_parser = Parser::new(body_lexer).unwrap();
+        let body = body_parser.parse().unwrap();
+
+        let defs = vec![("square".to_string(), vec!["x".to_string()], body)];
+        let program = Compiler::new().compile_program(defs, main).unwrap();
+
+        assert_eq!(program.user_funcs.len(), 1);
+        assert!(program.instructions.contains(&(OpCode::CallUser as u8)));
+    }
+
+    #[test]
+    fn test_compile_program_arity_mismatch() {
+        let lexer = Lexer::new("square(1, 2)");
+        let mut parser = Parser::new(lexer).unwrap();
+        let main = parser.parse().unwrap();
+
+        let body_lexer = Lexer::new("x ^ 2");
+        let mut body_parser = Parser::new(body_lexer).unwrap();
+        let body = body_parser.parse().unwrap();
+
+        let defs = vec![("square".to_string(), vec!["x".to_string()], body)];
+        let result = Compiler::new().compile_program(defs, main);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("expects 1 argument"));
+    }
+
     #[test]
     fn test_division_by_zero_not_folded() {
-        let compiler = Compiler::new();
+        let mut compiler = Compiler::new();
         let expr = Expr::Binary {
             op: BinaryOp::Div,
             left: Box::new(Expr::Number(1.0)),
             right: Box::new(Expr::Number(0.0)),
         };
-        let optimized = compiler.optimize_expr(expr);
+        let optimized = compiler.optimize_expr(expr).unwrap();
         // Should not fold division by zero
         assert!(matches!(optimized, Expr::Binary { .. }));
     }
+
+    fn parse(input: &str) -> Expr {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer).unwrap();
+        parser.parse().unwrap()
+    }
+
+    #[test]
+    fn test_compile_multi_shares_a_local_slot_for_a_repeated_subexpression() {
+        let program = Compiler::new()
+            .compile_multi(vec![parse("sqrt(x) * 2"), parse("sqrt(x) + 1")])
+            .unwrap();
+
+        // "sqrt(x)" recurs across both outputs, so it gets exactly one local
+        // slot, computed once and reloaded by the second output.
+        assert_eq!(program.local_count, 1);
+        assert_eq!(program.output_count, 2);
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 16.0);
+        let results = program.eval_multi(&ctx).unwrap();
+        assert_eq!(results, vec![8.0, 5.0]);
+    }
+
+    #[test]
+    fn test_compile_multi_with_no_shared_subexpressions_has_no_locals() {
+        let program = Compiler::new()
+            .compile_multi(vec![parse("x + 1"), parse("x * 2")])
+            .unwrap();
+        assert_eq!(program.local_count, 0);
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 5.0);
+        assert_eq!(program.eval_multi(&ctx).unwrap(), vec![6.0, 10.0]);
+    }
+
+    #[test]
+    fn test_compile_multi_single_output_matches_ordinary_compile() {
+        let multi = Compiler::new().compile_multi(vec![parse("x * x + 1")]).unwrap();
+        let single = compile_expr("x * x + 1");
+
+        let mut ctx = multi.create_context();
+        ctx.set_by_index(0, 4.0);
+        assert_eq!(multi.eval_multi(&ctx).unwrap(), vec![single.eval(&ctx).unwrap()]);
+    }
+
+    #[test]
+    fn test_ordinary_compile_never_emits_local_opcodes() {
+        // `cse_slots` is only ever populated by `compile_multi`, so a plain
+        // `compile` should produce local_count == 0 even when the same
+        // subexpression appears twice within one expression.
+        let program = compile_expr("sqrt(x) + sqrt(x)");
+        assert_eq!(program.local_count, 0);
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 9.0);
+        assert_eq!(program.eval(&ctx).unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_ordinary_compile_dups_a_repeated_subexpression() {
+        // "sqrt(x)" recurs immediately (nothing is pushed in between), so
+        // the second occurrence reuses the first via `Dup` instead of
+        // calling `sqrt` again.
+        let program = compile_expr("sqrt(x) + sqrt(x)");
+        assert_eq!(
+            program.instructions.iter().filter(|&&b| b == OpCode::Dup as u8).count(),
+            1
+        );
+        assert_eq!(
+            program.instructions.iter().filter(|&&b| b == OpCode::CallU8 as u8).count(),
+            1
+        );
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 9.0);
+        assert_eq!(program.eval(&ctx).unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_ordinary_compile_dups_a_subexpression_shared_across_a_larger_expression() {
+        // sin(t) + sin(t)^2 — the second `sin(t)` is still the stack top
+        // when its occurrence is reached, so it's a `Dup` too.
+        let program = compile_expr("sin(t) + sin(t) ^ 2");
+        assert_eq!(
+            program.instructions.iter().filter(|&&b| b == OpCode::Dup as u8).count(),
+            1
+        );
+        assert_eq!(
+            program.instructions.iter().filter(|&&b| b == OpCode::CallU8 as u8).count(),
+            1
+        );
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 0.5);
+        let t = 0.5_f64;
+        assert!((program.eval(&ctx).unwrap() - (t.sin() + t.sin().powi(2))).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ordinary_compile_does_not_dup_a_buried_repeat() {
+        // The first `sin(t)` is buried under the `5` by the time the second
+        // one is reached, so it isn't the stack top anymore and must be
+        // recomputed rather than (incorrectly) `Dup`'d.
+        let program = compile_expr("max(sin(t), 5) + sin(t)");
+        assert_eq!(
+            program.instructions.iter().filter(|&&b| b == OpCode::Dup as u8).count(),
+            0
+        );
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 0.5);
+        let t = 0.5_f64;
+        assert!((program.eval(&ctx).unwrap() - (t.sin().max(5.0) + t.sin())).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_ordinary_compile_does_not_dup_rand() {
+        // Each `rand()` occurrence must keep drawing an independent sample,
+        // so it's never replaced with a `Dup` of an earlier draw even though
+        // the two occurrences are textually identical.
+        let program = compile_expr("rand() - rand()");
+        assert_eq!(
+            program.instructions.iter().filter(|&&b| b == OpCode::Dup as u8).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_ordinary_compile_ternary_reconciles_the_stack_depth() {
+        // A ternary compiles its test/then/else branches one after another,
+        // but only one of them ever executes, so the Dup-CSE bookkeeping
+        // must not mistake a `then`/`else` value for something left on the
+        // stack by a sibling expression.
+        let program = compile_expr("(x > 0 ? x : -x) + (x > 0 ? x : -x)");
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, -3.0);
+        assert_eq!(program.eval(&ctx).unwrap(), 6.0);
+        ctx.set_by_index(0, 3.0);
+        assert_eq!(program.eval(&ctx).unwrap(), 6.0);
+    }
+
+    #[test]
+    fn test_compile_multi_does_not_hash_cons_rand() {
+        // Each `rand()` occurrence must keep drawing an independent sample,
+        // so it's never treated as a shared subexpression even though two
+        // occurrences are textually (and structurally) identical.
+        let program = Compiler::new()
+            .compile_multi(vec![parse("rand()"), parse("rand()")])
+            .unwrap();
+        assert_eq!(program.local_count, 0);
+    }
+
+    #[test]
+    fn test_ordinary_compile_does_not_dup_a_rand_buried_in_a_larger_expression() {
+        // `rand()` doesn't have to be at the root of the repeated subtree to
+        // be non-poolable: a `Cond`/`Binary` that merely *contains* a
+        // `rand()` call must also keep drawing an independent sample at
+        // every occurrence, not get `Dup`'d as if it were a pure repeat.
+        let program = compile_expr("(x > 0 ? rand() : 0) + (x > 0 ? rand() : 0)");
+        assert_eq!(
+            program.instructions.iter().filter(|&&b| b == OpCode::Dup as u8).count(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_compile_multi_does_not_hash_cons_a_rand_buried_in_a_larger_expression() {
+        // Same non-poolability requirement as the ordinary-compile case
+        // above, but for `compile_multi`'s hash-consing path.
+        let program = Compiler::new()
+            .compile_multi(vec![parse("rand() + x"), parse("rand() + x")])
+            .unwrap();
+        assert_eq!(program.local_count, 0);
+    }
 }
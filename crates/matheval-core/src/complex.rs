@@ -0,0 +1,569 @@
+//! A parallel compile/eval pipeline for complex-valued expressions.
+//!
+//! This reuses the shared `Lexer`/`Parser`/`Expr` front end but targets a
+//! separate `ComplexProgram`/`ComplexContext` so real-valued users keep the
+//! fast `f64` path untouched. The imaginary unit is written as the bare
+//! identifier `i` or `j` (e.g. `2 + 3 * i`, `sqrt(-1)` yields `i`).
+
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::bytecode::OpCode;
+use crate::lexer::Lexer;
+use crate::parser::Parser;
+use std::collections::HashMap;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// A complex number `re + im*i`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    pub fn from_real(re: f64) -> Self {
+        Self { re, im: 0.0 }
+    }
+
+    /// The imaginary unit, `i`.
+    pub const I: Complex = Complex { re: 0.0, im: 1.0 };
+
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    pub fn conj(self) -> Complex {
+        Complex::new(self.re, -self.im)
+    }
+
+    pub fn sqrt(self) -> Complex {
+        let r = self.abs();
+        let re = ((r + self.re) / 2.0).max(0.0).sqrt();
+        let im_mag = ((r - self.re) / 2.0).max(0.0).sqrt();
+        let im = if self.im < 0.0 { -im_mag } else { im_mag };
+        Complex::new(re, im)
+    }
+
+    pub fn ln(self) -> Complex {
+        Complex::new(self.abs().ln(), self.arg())
+    }
+
+    pub fn exp(self) -> Complex {
+        let r = self.re.exp();
+        Complex::new(r * self.im.cos(), r * self.im.sin())
+    }
+
+    pub fn sin(self) -> Complex {
+        Complex::new(self.re.sin() * self.im.cosh(), self.re.cos() * self.im.sinh())
+    }
+
+    pub fn cos(self) -> Complex {
+        Complex::new(self.re.cos() * self.im.cosh(), -self.re.sin() * self.im.sinh())
+    }
+
+    /// `self ^ other`, defined as `exp(other * ln(self))`.
+    pub fn powc(self, other: Complex) -> Complex {
+        if self.re == 0.0 && self.im == 0.0 {
+            return Complex::from_real(0.0);
+        }
+        (other * self.ln()).exp()
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+    fn add(self, rhs: Complex) -> Complex {
+        Complex::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+    fn sub(self, rhs: Complex) -> Complex {
+        Complex::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+    fn mul(self, rhs: Complex) -> Complex {
+        Complex::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+    fn div(self, rhs: Complex) -> Complex {
+        let denom = rhs.re * rhs.re + rhs.im * rhs.im;
+        Complex::new(
+            (self.re * rhs.re + self.im * rhs.im) / denom,
+            (self.im * rhs.re - self.re * rhs.im) / denom,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Complex;
+    fn neg(self) -> Complex {
+        Complex::new(-self.re, -self.im)
+    }
+}
+
+/// Function signature for complex-valued built-in functions.
+pub type ComplexBuiltinFn = fn(&[Complex]) -> Complex;
+
+/// Compiled program over the `Complex` value domain, mirroring `Program`.
+#[derive(Clone)]
+pub struct ComplexProgram {
+    pub instructions: Vec<u8>,
+    pub constants: Vec<Complex>,
+    pub var_names: Vec<String>,
+    pub func_table: Vec<ComplexBuiltinFn>,
+    pub func_names: Vec<String>,
+}
+
+impl ComplexProgram {
+    fn new() -> Self {
+        Self {
+            instructions: Vec::new(),
+            constants: Vec::new(),
+            var_names: Vec::new(),
+            func_table: Vec::new(),
+            func_names: Vec::new(),
+        }
+    }
+
+    /// Create a context pre-sized for this program.
+    pub fn create_context(&self) -> ComplexContext {
+        ComplexContext::with_capacity(self.var_names.len())
+    }
+
+    /// Evaluate the program with the given context.
+    pub fn eval(&self, context: &ComplexContext) -> Result<Complex, String> {
+        let mut vm = ComplexVM::new(self);
+        vm.run(context)
+    }
+}
+
+/// Variable storage for complex-valued evaluation, indexed like `Context`.
+#[derive(Debug, Clone)]
+pub struct ComplexContext {
+    values: Vec<Complex>,
+}
+
+impl ComplexContext {
+    pub fn new() -> Self {
+        Self { values: Vec::new() }
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: vec![Complex::from_real(0.0); capacity],
+        }
+    }
+
+    pub fn set_by_index(&mut self, index: usize, value: Complex) {
+        if index >= self.values.len() {
+            self.values.resize(index + 1, Complex::from_real(0.0));
+        }
+        self.values[index] = value;
+    }
+
+    pub fn get_by_index(&self, index: usize) -> Option<Complex> {
+        self.values.get(index).copied()
+    }
+
+    fn values(&self) -> &[Complex] {
+        &self.values
+    }
+}
+
+impl Default for ComplexContext {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn builtin_sqrt(args: &[Complex]) -> Complex {
+    args[0].sqrt()
+}
+fn builtin_ln(args: &[Complex]) -> Complex {
+    args[0].ln()
+}
+fn builtin_exp(args: &[Complex]) -> Complex {
+    args[0].exp()
+}
+fn builtin_sin(args: &[Complex]) -> Complex {
+    args[0].sin()
+}
+fn builtin_cos(args: &[Complex]) -> Complex {
+    args[0].cos()
+}
+fn builtin_conj(args: &[Complex]) -> Complex {
+    args[0].conj()
+}
+fn builtin_abs(args: &[Complex]) -> Complex {
+    Complex::from_real(args[0].abs())
+}
+
+/// High-level compiler for complex-valued expressions.
+///
+/// Parses with the shared `Lexer`/`Parser`, then lowers the resulting `Expr`
+/// into `ComplexProgram` bytecode. The bare identifiers `i`/`j` are treated
+/// as the imaginary unit rather than as variables to resolve.
+pub struct ComplexCompiler {
+    program: ComplexProgram,
+    var_map: HashMap<String, u16>,
+    func_map: HashMap<String, u16>,
+}
+
+impl ComplexCompiler {
+    pub fn new() -> Self {
+        Self {
+            program: ComplexProgram::new(),
+            var_map: HashMap::new(),
+            func_map: HashMap::new(),
+        }
+    }
+
+    /// Compile a complex-valued expression into bytecode.
+    pub fn compile(mut self, input: &str) -> Result<ComplexProgram, String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer).map_err(|e| e.to_string())?;
+        let ast = parser.parse().map_err(|e| e.to_string())?;
+        self.compile_expr(ast)?;
+        Ok(self.program)
+    }
+
+    fn compile_expr(&mut self, expr: Expr) -> Result<(), String> {
+        match expr {
+            Expr::Number(n) => {
+                let idx = self.add_constant(Complex::from_real(n));
+                self.emit_load_const(idx);
+            }
+            Expr::Variable(name) if name == "i" || name == "j" => {
+                let idx = self.add_constant(Complex::I);
+                self.emit_load_const(idx);
+            }
+            Expr::Variable(name) => {
+                let idx = self.resolve_var(name);
+                self.emit_load_var(idx);
+            }
+            Expr::Binary { op, left, right } => {
+                self.compile_expr(*left)?;
+                self.compile_expr(*right)?;
+                self.emit_binop(op)?;
+            }
+            Expr::Unary { op, expr } => {
+                self.compile_expr(*expr)?;
+                match op {
+                    UnaryOp::Neg => self.program.instructions.push(OpCode::Neg as u8),
+                    UnaryOp::Not => {
+                        return Err("Boolean negation is not supported for complex values".to_string())
+                    }
+                }
+            }
+            Expr::Call { func, args } => {
+                let arg_count = args.len() as u8;
+                for arg in args {
+                    self.compile_expr(arg)?;
+                }
+                let func_idx = self.resolve_func(&func)?;
+                self.program.instructions.push(OpCode::Call as u8);
+                self.emit_u16(func_idx);
+                self.program.instructions.push(arg_count);
+            }
+            Expr::Cond { .. } => {
+                return Err("Conditional expressions are not supported for complex values".to_string())
+            }
+            Expr::OpFunc(_) => {
+                return Err("Operator references are not supported for complex values".to_string())
+            }
+            Expr::Str(_) => {
+                return Err("String literals are not supported for complex values".to_string())
+            }
+        }
+        Ok(())
+    }
+
+    fn emit_binop(&mut self, op: BinaryOp) -> Result<(), String> {
+        let opcode = match op {
+            BinaryOp::Add => OpCode::Add,
+            BinaryOp::Sub => OpCode::Sub,
+            BinaryOp::Mul => OpCode::Mul,
+            BinaryOp::Div => OpCode::Div,
+            BinaryOp::Pow => OpCode::Pow,
+            _ => {
+                return Err(format!(
+                    "Operator {:?} is not supported for complex values",
+                    op
+                ))
+            }
+        };
+        self.program.instructions.push(opcode as u8);
+        Ok(())
+    }
+
+    fn add_constant(&mut self, value: Complex) -> u16 {
+        if let Some(idx) = self.program.constants.iter().position(|&c| c == value) {
+            return idx as u16;
+        }
+        let idx = self.program.constants.len() as u16;
+        self.program.constants.push(value);
+        idx
+    }
+
+    fn resolve_var(&mut self, name: String) -> u16 {
+        if let Some(&idx) = self.var_map.get(&name) {
+            return idx;
+        }
+        let idx = self.program.var_names.len() as u16;
+        self.program.var_names.push(name.clone());
+        self.var_map.insert(name, idx);
+        idx
+    }
+
+    fn resolve_func(&mut self, name: &str) -> Result<u16, String> {
+        if let Some(&idx) = self.func_map.get(name) {
+            return Ok(idx);
+        }
+        let func_ptr: ComplexBuiltinFn = match name {
+            "sqrt" => builtin_sqrt,
+            "ln" => builtin_ln,
+            "exp" => builtin_exp,
+            "sin" => builtin_sin,
+            "cos" => builtin_cos,
+            "conj" => builtin_conj,
+            "abs" => builtin_abs,
+            _ => return Err(format!("Unknown function: '{}'", name)),
+        };
+        let idx = self.program.func_names.len() as u16;
+        self.program.func_table.push(func_ptr);
+        self.program.func_names.push(name.to_string());
+        self.func_map.insert(name.to_string(), idx);
+        Ok(idx)
+    }
+
+    fn emit_load_const(&mut self, idx: u16) {
+        self.program.instructions.push(OpCode::LoadConst as u8);
+        self.emit_u16(idx);
+    }
+
+    fn emit_load_var(&mut self, idx: u16) {
+        self.program.instructions.push(OpCode::LoadVar as u8);
+        self.emit_u16(idx);
+    }
+
+    fn emit_u16(&mut self, value: u16) {
+        self.program.instructions.push((value >> 8) as u8);
+        self.program.instructions.push((value & 0xFF) as u8);
+    }
+}
+
+impl Default for ComplexCompiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Stack-based VM executing `ComplexProgram` bytecode, mirroring `vm::VM`.
+struct ComplexVM<'a> {
+    program: &'a ComplexProgram,
+    stack: Vec<Complex>,
+}
+
+impl<'a> ComplexVM<'a> {
+    fn new(program: &'a ComplexProgram) -> Self {
+        Self {
+            program,
+            stack: Vec::with_capacity(32),
+        }
+    }
+
+    fn run(&mut self, context: &ComplexContext) -> Result<Complex, String> {
+        if context.values().len() < self.program.var_names.len() {
+            return Err(format!(
+                "Context missing variables: expected {}, got {}",
+                self.program.var_names.len(),
+                context.values().len()
+            ));
+        }
+
+        let var_values = context.values();
+        let instructions = &self.program.instructions;
+        let constants = &self.program.constants;
+        let func_table = &self.program.func_table;
+
+        let mut pc = 0;
+        while pc < instructions.len() {
+            let opcode = instructions[pc];
+            pc += 1;
+
+            match opcode {
+                op if op == OpCode::LoadConst as u8 => {
+                    let idx = self.read_u16(instructions, &mut pc);
+                    self.stack.push(constants[idx as usize]);
+                }
+                op if op == OpCode::LoadVar as u8 => {
+                    let idx = self.read_u16(instructions, &mut pc);
+                    self.stack.push(var_values[idx as usize]);
+                }
+                op if op == OpCode::Add as u8 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(a + b);
+                }
+                op if op == OpCode::Sub as u8 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(a - b);
+                }
+                op if op == OpCode::Mul as u8 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(a * b);
+                }
+                op if op == OpCode::Div as u8 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(a / b);
+                }
+                op if op == OpCode::Pow as u8 => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(a.powc(b));
+                }
+                op if op == OpCode::Neg as u8 => {
+                    let a = self.pop()?;
+                    self.stack.push(-a);
+                }
+                op if op == OpCode::Call as u8 => {
+                    let func_idx = self.read_u16(instructions, &mut pc) as usize;
+                    let arg_count = instructions[pc] as usize;
+                    pc += 1;
+
+                    if func_idx >= func_table.len() {
+                        return Err(format!("Invalid function index: {}", func_idx));
+                    }
+
+                    let stack_len = self.stack.len();
+                    if stack_len < arg_count {
+                        return Err("Stack underflow in function call".to_string());
+                    }
+
+                    let args_start = stack_len - arg_count;
+                    let result = func_table[func_idx](&self.stack[args_start..]);
+
+                    self.stack.truncate(args_start);
+                    self.stack.push(result);
+                }
+                _ => return Err(format!("Unknown opcode: {}", opcode)),
+            }
+        }
+
+        self.pop()
+    }
+
+    #[inline]
+    fn read_u16(&self, instructions: &[u8], pc: &mut usize) -> u16 {
+        let high = instructions[*pc] as u16;
+        let low = instructions[*pc + 1] as u16;
+        *pc += 2;
+        (high << 8) | low
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Result<Complex, String> {
+        self.stack.pop().ok_or_else(|| "Stack underflow".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_complex_arithmetic() {
+        let a = Complex::new(1.0, 2.0);
+        let b = Complex::new(3.0, -1.0);
+        assert_eq!(a + b, Complex::new(4.0, 1.0));
+        assert_eq!(a - b, Complex::new(-2.0, 3.0));
+        assert_eq!(a * b, Complex::new(5.0, 5.0));
+    }
+
+    #[test]
+    fn test_complex_division() {
+        let a = Complex::new(1.0, 0.0);
+        let b = Complex::new(0.0, 1.0);
+        // 1 / i = -i
+        let result = a / b;
+        assert!((result.re - 0.0).abs() < 1e-10);
+        assert!((result.im - (-1.0)).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_sqrt_of_negative_one_is_i() {
+        let result = Complex::from_real(-1.0).sqrt();
+        assert!((result.re - 0.0).abs() < 1e-10);
+        assert!((result.im - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_compile_and_eval_pure_real_expression() {
+        let program = ComplexCompiler::new().compile("2 + 3 * 4").unwrap();
+        let ctx = program.create_context();
+        let result = program.eval(&ctx).unwrap();
+        assert_eq!(result, Complex::from_real(14.0));
+    }
+
+    #[test]
+    fn test_compile_and_eval_imaginary_literal() {
+        // 2 + 3 * i
+        let program = ComplexCompiler::new().compile("2 + 3 * i").unwrap();
+        let ctx = program.create_context();
+        let result = program.eval(&ctx).unwrap();
+        assert_eq!(result, Complex::new(2.0, 3.0));
+    }
+
+    #[test]
+    fn test_compile_and_eval_sqrt_negative() {
+        let program = ComplexCompiler::new().compile("sqrt(-1)").unwrap();
+        let ctx = program.create_context();
+        let result = program.eval(&ctx).unwrap();
+        assert!((result.re - 0.0).abs() < 1e-10);
+        assert!((result.im - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_compile_with_variable() {
+        let program = ComplexCompiler::new().compile("z * z").unwrap();
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, Complex::new(0.0, 1.0)); // z = i
+        let result = program.eval(&ctx).unwrap();
+        // i * i = -1
+        assert!((result.re - (-1.0)).abs() < 1e-10);
+        assert!(result.im.abs() < 1e-10);
+    }
+
+    #[test]
+    fn test_unknown_function_errors() {
+        let result = ComplexCompiler::new().compile("bogus(z)");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_conditional_unsupported() {
+        let result = ComplexCompiler::new().compile("z > 0 ? 1 : 0");
+        assert!(result.is_err());
+    }
+}
@@ -24,12 +24,30 @@ impl fmt::Display for Position {
     }
 }
 
+/// A range in the source text, from the start of a token to just past its
+/// end. Produced by the lexer for every token so the parser can attach an
+/// exact location to its errors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl Span {
+    pub fn new(start: Position, end: Position) -> Self {
+        Self { start, end }
+    }
+}
+
 /// Error kinds with detailed context
 #[derive(Debug, Clone, PartialEq)]
 pub enum ErrorKind {
     // Lexer errors
     UnexpectedCharacter(char),
     InvalidNumber(String),
+    MalformedExponent(String),
+    UnterminatedString,
+    InvalidEscapeSequence(char),
     
     // Parser errors
     UnexpectedToken(String),
@@ -39,7 +57,8 @@ pub enum ErrorKind {
     
     // Compiler errors
     UnknownFunction(String),
-    
+    RecursionLimitExceeded(u32),
+
     // Runtime errors
     DivisionByZero,
     StackUnderflow,
@@ -66,6 +85,15 @@ impl fmt::Display for ErrorKind {
             ErrorKind::InvalidNumber(s) => {
                 write!(f, "Invalid number format: '{}'", s)
             }
+            ErrorKind::MalformedExponent(s) => {
+                write!(f, "Malformed exponent in number: '{}'", s)
+            }
+            ErrorKind::UnterminatedString => {
+                write!(f, "Unterminated string literal")
+            }
+            ErrorKind::InvalidEscapeSequence(ch) => {
+                write!(f, "Invalid escape sequence: '\\{}'", ch)
+            }
             ErrorKind::UnexpectedToken(token) => {
                 write!(f, "Unexpected token: {}", token)
             }
@@ -81,6 +109,9 @@ impl fmt::Display for ErrorKind {
             ErrorKind::UnknownFunction(name) => {
                 write!(f, "Unknown function: '{}'", name)
             }
+            ErrorKind::RecursionLimitExceeded(max_depth) => {
+                write!(f, "Recursion limit exceeded (max call depth {})", max_depth)
+            }
             ErrorKind::DivisionByZero => {
                 write!(f, "Division by zero")
             }
@@ -151,6 +182,18 @@ impl Error {
         Self::new(ErrorKind::UnexpectedCharacter(ch))
             .with_position(position)
     }
+
+    pub fn malformed_exponent(partial: &str, position: Position) -> Self {
+        Self::new(ErrorKind::MalformedExponent(partial.to_string())).with_position(position)
+    }
+
+    pub fn unterminated_string(position: Position) -> Self {
+        Self::new(ErrorKind::UnterminatedString).with_position(position)
+    }
+
+    pub fn invalid_escape_sequence(ch: char, position: Position) -> Self {
+        Self::new(ErrorKind::InvalidEscapeSequence(ch)).with_position(position)
+    }
     
     /// Create a parser error
     pub fn expected_token(expected: &str, found: &str, position: Position) -> Self {
@@ -160,7 +203,24 @@ impl Error {
         })
         .with_position(position)
     }
-    
+
+    pub fn unexpected_token(found: &str, position: Position) -> Self {
+        Self::new(ErrorKind::UnexpectedToken(found.to_string())).with_position(position)
+    }
+
+    pub fn missing_closing_paren(position: Position) -> Self {
+        Self::new(ErrorKind::MissingClosingParen).with_position(position)
+    }
+
+    pub fn missing_function_closing_paren(func: &str, position: Position) -> Self {
+        Self::new(ErrorKind::MissingFunctionClosingParen(func.to_string())).with_position(position)
+    }
+
+    /// Create a compiler error
+    pub fn unknown_function(name: &str, position: Position) -> Self {
+        Self::new(ErrorKind::UnknownFunction(name.to_string())).with_position(position)
+    }
+
     /// Create a runtime error
     pub fn division_by_zero() -> Self {
         Self::new(ErrorKind::DivisionByZero)
@@ -241,6 +301,9 @@ impl Error {
             ErrorKind::UnknownFunction(_) => {
                 Some("Available functions: sin, cos, tan, sqrt, abs, floor, ceil, round, exp, ln, log10, max, min")
             }
+            ErrorKind::RecursionLimitExceeded(_) => {
+                Some("Check for unbounded recursion between user-defined functions")
+            }
             _ => None,
         }
     }
@@ -286,6 +349,12 @@ mod tests {
         assert!(display.contains("^"));
     }
 
+    #[test]
+    fn test_recursion_limit_exceeded_display() {
+        let err = ErrorKind::RecursionLimitExceeded(256);
+        assert_eq!(err.to_string(), "Recursion limit exceeded (max call depth 256)");
+    }
+
     #[test]
     fn test_error_hints() {
         let err = Error::division_by_zero();
@@ -1,180 +1,505 @@
+use crate::ast::BinaryOp;
+use crate::error::{Error, ErrorKind, Position, Span};
 use crate::token::Token;
 use std::iter::Peekable;
 use std::str::Chars;
 
+/// Maps the single character following a `\` to the operator it references,
+/// e.g. `\+` -> `BinaryOp::Add`. Two-character operators (`<=`, `==`, ...)
+/// aren't reachable this way since an op-ref only ever consumes two chars.
+fn op_ref_for_char(c: char) -> Option<BinaryOp> {
+    match c {
+        '+' => Some(BinaryOp::Add),
+        '-' => Some(BinaryOp::Sub),
+        '*' => Some(BinaryOp::Mul),
+        '/' => Some(BinaryOp::Div),
+        '^' => Some(BinaryOp::Pow),
+        '<' => Some(BinaryOp::Lt),
+        '>' => Some(BinaryOp::Gt),
+        _ => None,
+    }
+}
+
 pub struct Lexer<'a> {
+    source: &'a str,
     input: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+    offset: usize,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Self {
+            source: input,
             input: input.chars().peekable(),
+            line: 1,
+            column: 1,
+            offset: 0,
+        }
+    }
+
+    /// The original source text, kept around so errors can attach a visual
+    /// pointer via `Error::with_source`.
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    fn current_position(&self) -> Position {
+        Position::new(self.line, self.column, self.offset)
+    }
+
+    /// Consumes and returns the next character, updating `line`/`column`/
+    /// `offset` as it goes (bumping `line` and resetting `column` on `\n`).
+    fn bump(&mut self) -> Option<char> {
+        let c = self.input.next()?;
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
         }
+        Some(c)
     }
 
-    pub fn next_token(&mut self) -> Result<Token, String> {
+    pub fn next_token(&mut self) -> Result<(Token, Span), Error> {
         self.skip_whitespace();
+        let start = self.current_position();
 
-        match self.input.peek() {
-            None => Ok(Token::EOF),
+        let token = match self.input.peek() {
+            None => Token::EOF,
             Some(&c) => match c {
-                '+' => { self.input.next(); Ok(Token::Plus) }
-                '-' => { self.input.next(); Ok(Token::Minus) }
-                '*' => { self.input.next(); Ok(Token::Star) }
-                '/' => { self.input.next(); Ok(Token::Slash) }
-                '^' => { self.input.next(); Ok(Token::Caret) }
-                '(' => { self.input.next(); Ok(Token::LParen) }
-                ')' => { self.input.next(); Ok(Token::RParen) }
-                ',' => { self.input.next(); Ok(Token::Comma) }
-                '0'..='9' | '.' => self.read_number(),
+                '+' => { self.bump(); Token::Plus }
+                '-' => { self.bump(); Token::Minus }
+                '*' => { self.bump(); Token::Star }
+                '/' => { self.bump(); Token::Slash }
+                '^' => { self.bump(); Token::Caret }
+                '(' => { self.bump(); Token::LParen }
+                ')' => { self.bump(); Token::RParen }
+                ',' => { self.bump(); Token::Comma }
+                ';' => { self.bump(); Token::Semicolon }
+                '<' => {
+                    self.bump();
+                    if self.input.peek() == Some(&'=') {
+                        self.bump();
+                        Token::Le
+                    } else {
+                        Token::Lt
+                    }
+                }
+                '>' => {
+                    self.bump();
+                    if self.input.peek() == Some(&'=') {
+                        self.bump();
+                        Token::Ge
+                    } else {
+                        Token::Gt
+                    }
+                }
+                '=' => {
+                    self.bump();
+                    if self.input.peek() == Some(&'=') {
+                        self.bump();
+                        Token::EqEq
+                    } else {
+                        Token::Assign
+                    }
+                }
+                '!' => {
+                    self.bump();
+                    if self.input.peek() == Some(&'=') {
+                        self.bump();
+                        Token::NotEq
+                    } else {
+                        Token::Bang
+                    }
+                }
+                '&' => {
+                    self.bump();
+                    if self.input.peek() == Some(&'&') {
+                        self.bump();
+                        Token::AndAnd
+                    } else {
+                        return Err(Error::unexpected_char('&', start)
+                            .with_source(self.source.to_string()));
+                    }
+                }
+                '|' => {
+                    self.bump();
+                    if self.input.peek() == Some(&'|') {
+                        self.bump();
+                        Token::OrOr
+                    } else {
+                        return Err(Error::unexpected_char('|', start)
+                            .with_source(self.source.to_string()));
+                    }
+                }
+                '?' => { self.bump(); Token::Question }
+                ':' => { self.bump(); Token::Colon }
+                '\\' => {
+                    self.bump();
+                    match self.input.peek().copied().and_then(op_ref_for_char) {
+                        Some(op) => {
+                            self.bump();
+                            Token::OpRef(op)
+                        }
+                        None => {
+                            return Err(Error::unexpected_char('\\', start)
+                                .with_source(self.source.to_string()));
+                        }
+                    }
+                }
+                '0'..='9' | '.' => self.read_number(start)?,
                 'a'..='z' | 'A'..='Z' | '_' => self.read_identifier(),
-                _ => Err(format!("Unexpected character: {}", c)),
-            }
-        }
+                '"' => self.read_string(start)?,
+                _ => {
+                    self.bump();
+                    return Err(Error::unexpected_char(c, start)
+                        .with_source(self.source.to_string()));
+                }
+            },
+        };
+
+        let end = self.current_position();
+        Ok((token, Span::new(start, end)))
     }
 
     fn skip_whitespace(&mut self) {
         while let Some(&c) = self.input.peek() {
             if c.is_whitespace() {
-                self.input.next();
+                self.bump();
             } else {
                 break;
             }
         }
     }
 
-    fn read_number(&mut self) -> Result<Token, String> {
+    fn read_number(&mut self, start: Position) -> Result<Token, Error> {
         let mut number_str = String::new();
+
+        // `0x...`/`0b...` integer literals. Peeking one char ahead can't tell
+        // a hex/binary prefix from a plain `0`, so speculatively consume the
+        // leading `0` and only commit to the normal path if it isn't one.
+        if self.input.peek() == Some(&'0') {
+            self.bump();
+            match self.input.peek() {
+                Some(&'x') | Some(&'X') => {
+                    self.bump();
+                    return self.read_radix_number(start, 16, "0x");
+                }
+                Some(&'b') | Some(&'B') => {
+                    self.bump();
+                    return self.read_radix_number(start, 2, "0b");
+                }
+                _ => number_str.push('0'),
+            }
+        }
+
         let mut has_decimal = false;
 
         while let Some(&c) = self.input.peek() {
-            if c.is_digit(10) {
+            if c == '_' {
+                self.bump();
+            } else if c.is_digit(10) {
                 number_str.push(c);
-                self.input.next();
+                self.bump();
             } else if c == '.' {
                 if has_decimal {
-                    return Err("Invalid number: multiple decimal points".to_string());
+                    return Err(Error::new(ErrorKind::InvalidNumber(number_str))
+                        .with_position(start)
+                        .with_source(self.source.to_string()));
                 }
                 has_decimal = true;
                 number_str.push(c);
-                self.input.next();
+                self.bump();
+            } else if c == 'e' || c == 'E' {
+                number_str.push(c);
+                self.bump();
+                self.read_exponent(start, &mut number_str)?;
+                break;
             } else {
                 break;
             }
         }
 
-        number_str.parse::<f64>()
+        number_str
+            .parse::<f64>()
             .map(Token::Number)
-            .map_err(|_| format!("Invalid number format: {}", number_str))
+            .map_err(|_| {
+                Error::new(ErrorKind::InvalidNumber(number_str))
+                    .with_position(start)
+                    .with_source(self.source.to_string())
+            })
     }
 
-    fn read_identifier(&mut self) -> Result<Token, String> {
+    /// Consumes an exponent suffix (`e`/`E` already consumed) into
+    /// `number_str`: an optional sign followed by one or more digits, with
+    /// `_` separators skipped. Errors on a bare `e` with no digits.
+    fn read_exponent(&mut self, start: Position, number_str: &mut String) -> Result<(), Error> {
+        if let Some(&sign) = self.input.peek() {
+            if sign == '+' || sign == '-' {
+                number_str.push(sign);
+                self.bump();
+            }
+        }
+
+        let mut exponent_digits = 0;
+        while let Some(&c) = self.input.peek() {
+            if c == '_' {
+                self.bump();
+            } else if c.is_digit(10) {
+                number_str.push(c);
+                self.bump();
+                exponent_digits += 1;
+            } else {
+                break;
+            }
+        }
+
+        if exponent_digits == 0 {
+            return Err(Error::malformed_exponent(number_str, start)
+                .with_source(self.source.to_string()));
+        }
+        Ok(())
+    }
+
+    /// Consumes the digits of a `0x`/`0b` literal (prefix already consumed)
+    /// and parses them as an integer of the given `radix`, widening to `f64`.
+    fn read_radix_number(&mut self, start: Position, radix: u32, prefix: &str) -> Result<Token, Error> {
+        let mut digits = String::new();
+        while let Some(&c) = self.input.peek() {
+            if c == '_' {
+                self.bump();
+            } else if c.is_digit(radix) {
+                digits.push(c);
+                self.bump();
+            } else {
+                break;
+            }
+        }
+
+        if digits.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidNumber(prefix.to_string()))
+                .with_position(start)
+                .with_source(self.source.to_string()));
+        }
+
+        u64::from_str_radix(&digits, radix)
+            .map(|v| Token::Number(v as f64))
+            .map_err(|_| {
+                Error::new(ErrorKind::InvalidNumber(format!("{}{}", prefix, digits)))
+                    .with_position(start)
+                    .with_source(self.source.to_string())
+            })
+    }
+
+    /// Consumes a double-quoted string literal (opening `"` already peeked,
+    /// not yet consumed), resolving `\n`, `\t`, `\\`, and `\"` escapes.
+    /// Errors on an unrecognized escape or on reaching EOF before the
+    /// closing quote.
+    fn read_string(&mut self, start: Position) -> Result<Token, Error> {
+        self.bump(); // opening quote
+
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                None => {
+                    return Err(Error::unterminated_string(start)
+                        .with_source(self.source.to_string()));
+                }
+                Some('"') => break,
+                Some('\\') => {
+                    let escape_pos = self.current_position();
+                    match self.bump() {
+                        Some('n') => value.push('\n'),
+                        Some('t') => value.push('\t'),
+                        Some('\\') => value.push('\\'),
+                        Some('"') => value.push('"'),
+                        Some(other) => {
+                            return Err(Error::invalid_escape_sequence(other, escape_pos)
+                                .with_source(self.source.to_string()));
+                        }
+                        None => {
+                            return Err(Error::unterminated_string(start)
+                                .with_source(self.source.to_string()));
+                        }
+                    }
+                }
+                Some(c) => value.push(c),
+            }
+        }
+
+        Ok(Token::Str(value))
+    }
+
+    fn read_identifier(&mut self) -> Token {
         let mut ident_str = String::new();
 
         while let Some(&c) = self.input.peek() {
             if c.is_alphanumeric() || c == '_' {
                 ident_str.push(c);
-                self.input.next();
+                self.bump();
             } else {
                 break;
             }
         }
 
-        Ok(Token::Identifier(ident_str))
+        Token::Identifier(ident_str)
     }
 }
 
 impl<'a> Iterator for Lexer<'a> {
-    type Item = Result<Token, String>;
+    type Item = Result<(Token, Span), Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
         let token = self.next_token();
         match token {
-            Ok(Token::EOF) => None,
+            Ok((Token::EOF, _)) => None,
             _ => Some(token),
         }
     }
 }
 
+/// Drains `input` into a vector of `(Token, Span)` pairs, terminated by an
+/// `EOF` token (unlike the `Iterator` impl, which stops before it).
+pub fn lex(input: &str) -> Result<Vec<(Token, Span)>, Error> {
+    let mut lexer = Lexer::new(input);
+    let mut tokens = Vec::new();
+    loop {
+        let (token, span) = lexer.next_token()?;
+        let is_eof = token == Token::EOF;
+        tokens.push((token, span));
+        if is_eof {
+            break;
+        }
+    }
+    Ok(tokens)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn tokens(input: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(input);
+        let mut out = Vec::new();
+        loop {
+            let (token, _) = lexer.next_token().unwrap();
+            if token == Token::EOF {
+                out.push(token);
+                break;
+            }
+            out.push(token);
+        }
+        out
+    }
+
     #[test]
     fn test_lexer_numbers() {
         let mut lexer = Lexer::new("123 45.67 0.5 .25");
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(123.0));
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(45.67));
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(0.5));
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(0.25));
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(123.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(45.67));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(0.5));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(0.25));
+        assert_eq!(lexer.next_token().unwrap().0, Token::EOF);
     }
 
     #[test]
     fn test_lexer_operators() {
         let mut lexer = Lexer::new("+ - * / ^");
-        assert_eq!(lexer.next_token().unwrap(), Token::Plus);
-        assert_eq!(lexer.next_token().unwrap(), Token::Minus);
-        assert_eq!(lexer.next_token().unwrap(), Token::Star);
-        assert_eq!(lexer.next_token().unwrap(), Token::Slash);
-        assert_eq!(lexer.next_token().unwrap(), Token::Caret);
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Plus);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Minus);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Star);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Slash);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Caret);
+        assert_eq!(lexer.next_token().unwrap().0, Token::EOF);
     }
 
     #[test]
     fn test_lexer_parentheses() {
         let mut lexer = Lexer::new("( )");
-        assert_eq!(lexer.next_token().unwrap(), Token::LParen);
-        assert_eq!(lexer.next_token().unwrap(), Token::RParen);
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(lexer.next_token().unwrap().0, Token::LParen);
+        assert_eq!(lexer.next_token().unwrap().0, Token::RParen);
+        assert_eq!(lexer.next_token().unwrap().0, Token::EOF);
+    }
+
+    #[test]
+    fn test_lexer_semicolon() {
+        let mut lexer = Lexer::new("f(x) = x; f(1)");
+        let kinds: Vec<Token> = std::iter::from_fn(|| {
+            let (token, _) = lexer.next_token().unwrap();
+            if token == Token::EOF { None } else { Some(token) }
+        })
+        .collect();
+        assert!(kinds.contains(&Token::Semicolon));
+    }
+
+    #[test]
+    fn test_lexer_semicolon_inside_string_literal_is_not_a_token() {
+        // `;` inside a string literal is part of the string's content, not a
+        // standalone `Semicolon` token.
+        let mut lexer = Lexer::new(r#""a;b""#);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Str("a;b".to_string()));
+        assert_eq!(lexer.next_token().unwrap().0, Token::EOF);
     }
 
     #[test]
     fn test_lexer_identifiers() {
-        let mut lexer = Lexer::new("x foo bar_123 _test");
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("x".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("foo".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("bar_123".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("_test".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(
+            tokens("x foo bar_123 _test"),
+            vec![
+                Token::Identifier("x".to_string()),
+                Token::Identifier("foo".to_string()),
+                Token::Identifier("bar_123".to_string()),
+                Token::Identifier("_test".to_string()),
+                Token::EOF,
+            ]
+        );
     }
 
     #[test]
     fn test_lexer_expression() {
-        let mut lexer = Lexer::new("x + 2 * sin(y)");
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("x".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::Plus);
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(2.0));
-        assert_eq!(lexer.next_token().unwrap(), Token::Star);
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("sin".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::LParen);
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("y".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::RParen);
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(
+            tokens("x + 2 * sin(y)"),
+            vec![
+                Token::Identifier("x".to_string()),
+                Token::Plus,
+                Token::Number(2.0),
+                Token::Star,
+                Token::Identifier("sin".to_string()),
+                Token::LParen,
+                Token::Identifier("y".to_string()),
+                Token::RParen,
+                Token::EOF,
+            ]
+        );
     }
 
     #[test]
     fn test_lexer_whitespace() {
-        let mut lexer = Lexer::new("  1  +  2  ");
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(1.0));
-        assert_eq!(lexer.next_token().unwrap(), Token::Plus);
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(2.0));
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(
+            tokens("  1  +  2  "),
+            vec![Token::Number(1.0), Token::Plus, Token::Number(2.0), Token::EOF]
+        );
     }
 
     #[test]
     fn test_lexer_function_call() {
-        let mut lexer = Lexer::new("max(1, 2, 3)");
-        assert_eq!(lexer.next_token().unwrap(), Token::Identifier("max".to_string()));
-        assert_eq!(lexer.next_token().unwrap(), Token::LParen);
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(1.0));
-        assert_eq!(lexer.next_token().unwrap(), Token::Comma);
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(2.0));
-        assert_eq!(lexer.next_token().unwrap(), Token::Comma);
-        assert_eq!(lexer.next_token().unwrap(), Token::Number(3.0));
-        assert_eq!(lexer.next_token().unwrap(), Token::RParen);
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(
+            tokens("max(1, 2, 3)"),
+            vec![
+                Token::Identifier("max".to_string()),
+                Token::LParen,
+                Token::Number(1.0),
+                Token::Comma,
+                Token::Number(2.0),
+                Token::Comma,
+                Token::Number(3.0),
+                Token::RParen,
+                Token::EOF,
+            ]
+        );
     }
 
     #[test]
@@ -188,7 +513,7 @@ mod tests {
         let mut lexer = Lexer::new("@");
         let result = lexer.next_token();
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("Unexpected character"));
+        assert!(result.unwrap_err().to_string().contains("Unexpected character"));
     }
 
     #[test]
@@ -196,14 +521,188 @@ mod tests {
         let lexer = Lexer::new("1 + 2");
         let tokens: Vec<_> = lexer.collect();
         assert_eq!(tokens.len(), 3);
-        assert_eq!(tokens[0].as_ref().unwrap(), &Token::Number(1.0));
-        assert_eq!(tokens[1].as_ref().unwrap(), &Token::Plus);
-        assert_eq!(tokens[2].as_ref().unwrap(), &Token::Number(2.0));
+        assert_eq!(tokens[0].as_ref().unwrap().0, Token::Number(1.0));
+        assert_eq!(tokens[1].as_ref().unwrap().0, Token::Plus);
+        assert_eq!(tokens[2].as_ref().unwrap().0, Token::Number(2.0));
     }
 
     #[test]
     fn test_lexer_empty_input() {
         let mut lexer = Lexer::new("");
-        assert_eq!(lexer.next_token().unwrap(), Token::EOF);
+        assert_eq!(lexer.next_token().unwrap().0, Token::EOF);
+    }
+
+    #[test]
+    fn test_lexer_comparison_operators() {
+        assert_eq!(
+            tokens("< > <= >= == !="),
+            vec![
+                Token::Lt,
+                Token::Gt,
+                Token::Le,
+                Token::Ge,
+                Token::EqEq,
+                Token::NotEq,
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_boolean_operators() {
+        assert_eq!(
+            tokens("&& || !"),
+            vec![Token::AndAnd, Token::OrOr, Token::Bang, Token::EOF]
+        );
+    }
+
+    #[test]
+    fn test_lexer_ternary_tokens() {
+        let mut lexer = Lexer::new("x > 0 ? 1 : 0");
+        assert_eq!(lexer.next_token().unwrap().0, Token::Identifier("x".to_string()));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Gt);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(0.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Question);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(1.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Colon);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(0.0));
+    }
+
+    #[test]
+    fn test_lexer_single_ampersand_errors() {
+        let mut lexer = Lexer::new("&");
+        assert!(lexer.next_token().is_err());
+    }
+
+    #[test]
+    fn test_lexer_single_equals_is_assign() {
+        let mut lexer = Lexer::new("=");
+        assert_eq!(lexer.next_token().unwrap().0, Token::Assign);
+    }
+
+    #[test]
+    fn test_lexer_function_definition_tokens() {
+        let mut lexer = Lexer::new("square(x) = x ^ 2");
+        assert_eq!(lexer.next_token().unwrap().0, Token::Identifier("square".to_string()));
+        assert_eq!(lexer.next_token().unwrap().0, Token::LParen);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Identifier("x".to_string()));
+        assert_eq!(lexer.next_token().unwrap().0, Token::RParen);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Assign);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Identifier("x".to_string()));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Caret);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(2.0));
+    }
+
+    #[test]
+    fn test_lexer_tracks_line_and_column() {
+        let mut lexer = Lexer::new("1\n  22");
+        let (_, span1) = lexer.next_token().unwrap();
+        assert_eq!(span1.start, Position::new(1, 1, 0));
+
+        let (_, span2) = lexer.next_token().unwrap();
+        assert_eq!(span2.start, Position::new(2, 3, 4));
+        assert_eq!(span2.end, Position::new(2, 5, 6));
+    }
+
+    #[test]
+    fn test_lex_convenience_includes_eof() {
+        let tokens = lex("1 + 2").unwrap();
+        assert_eq!(tokens.len(), 4);
+        assert_eq!(tokens.last().unwrap().0, Token::EOF);
+    }
+
+    #[test]
+    fn test_lexer_scientific_notation() {
+        let mut lexer = Lexer::new("6.022e23 1E-10 5e+2");
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(6.022e23));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(1e-10));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(5e2));
+    }
+
+    #[test]
+    fn test_lexer_malformed_exponent_errors() {
+        let mut lexer = Lexer::new("1e");
+        let err = lexer.next_token().unwrap_err();
+        assert!(err.to_string().contains("Malformed exponent"));
+    }
+
+    #[test]
+    fn test_lexer_underscore_separators() {
+        let mut lexer = Lexer::new("1_000_000 3.14_159");
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(1_000_000.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(3.14159));
+    }
+
+    #[test]
+    fn test_lexer_hex_and_binary_literals() {
+        let mut lexer = Lexer::new("0xff 0b1010 0x1_0");
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(255.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(10.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(16.0));
+    }
+
+    #[test]
+    fn test_lexer_plain_zero_still_works() {
+        let mut lexer = Lexer::new("0 0.5");
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(0.0));
+        assert_eq!(lexer.next_token().unwrap().0, Token::Number(0.5));
+    }
+
+    #[test]
+    fn test_lexer_operator_references() {
+        assert_eq!(
+            tokens("\\+ \\- \\* \\/ \\^ \\< \\>"),
+            vec![
+                Token::OpRef(BinaryOp::Add),
+                Token::OpRef(BinaryOp::Sub),
+                Token::OpRef(BinaryOp::Mul),
+                Token::OpRef(BinaryOp::Div),
+                Token::OpRef(BinaryOp::Pow),
+                Token::OpRef(BinaryOp::Lt),
+                Token::OpRef(BinaryOp::Gt),
+                Token::EOF,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lexer_operator_reference_unknown_operator_errors() {
+        let mut lexer = Lexer::new("\\q");
+        assert!(lexer.next_token().is_err());
+    }
+
+    #[test]
+    fn test_lexer_string_literal() {
+        let mut lexer = Lexer::new(r#""hello""#);
+        assert_eq!(lexer.next_token().unwrap().0, Token::Str("hello".to_string()));
+    }
+
+    #[test]
+    fn test_lexer_string_literal_escapes() {
+        let mut lexer = Lexer::new(r#""a\nb\tc\\d\"e""#);
+        assert_eq!(
+            lexer.next_token().unwrap().0,
+            Token::Str("a\nb\tc\\d\"e".to_string())
+        );
+    }
+
+    #[test]
+    fn test_lexer_unterminated_string_errors() {
+        let mut lexer = Lexer::new(r#""hello"#);
+        let err = lexer.next_token().unwrap_err();
+        assert!(err.to_string().contains("Unterminated string"));
+    }
+
+    #[test]
+    fn test_lexer_invalid_escape_sequence_errors() {
+        let mut lexer = Lexer::new(r#""\q""#);
+        let err = lexer.next_token().unwrap_err();
+        assert!(err.to_string().contains("Invalid escape sequence"));
+    }
+
+    #[test]
+    fn test_lex_error_has_source_context() {
+        let err = lex("1 + @").unwrap_err();
+        assert!(err.to_string().contains("1 + @"));
     }
 }
@@ -5,32 +5,299 @@ mod parser;
 mod bytecode;
 mod compiler;
 mod vm;
+mod complex;
+mod error;
+mod value;
+mod num;
+mod rng;
+mod registers;
+mod packed;
 
-pub use vm::Context;
+pub use vm::{Context, SimStats};
 pub use bytecode::Program;
+pub use bytecode::{BuiltinFn, FunctionMetadata};
+pub use complex::{Complex, ComplexCompiler, ComplexContext, ComplexProgram};
+pub use value::Value;
+pub use num::Num;
+pub use registers::RegisterProgram;
+pub use packed::PackedProgram;
+pub use ast::{BinaryOp, Expr, UnaryOp};
 
+use error::Span;
 use lexer::Lexer;
 use parser::Parser;
 use compiler::Compiler as BytecodeCompiler;
+use token::Token;
 use vm::VM;
 
 /// High-level compiler interface
-pub struct Compiler;
+pub struct Compiler {
+    max_depth: Option<u32>,
+    custom_funcs: Vec<(String, BuiltinFn, FunctionMetadata)>,
+}
 
 impl Compiler {
     pub fn new() -> Self {
-        Self
+        Self {
+            max_depth: None,
+            custom_funcs: Vec::new(),
+        }
+    }
+
+    /// Overrides the maximum AST nesting depth compilation will recurse
+    /// through before aborting with an error (see `BytecodeCompiler`'s
+    /// `with_max_depth` for the default). Applies to every `compile*` method
+    /// called on this `Compiler`.
+    pub fn with_max_depth(mut self, max_depth: u32) -> Self {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    /// Registers a custom function under `name`, so that calls to it compile
+    /// just like a builtin, with `meta`'s arity checked against each call
+    /// site at compile time. Lets callers inject domain functions (e.g. a
+    /// Black-Scholes `cdf`, `clamp`, `lerp`) without forking the crate.
+    /// Applies to every `compile*` method called on this `Compiler`.
+    pub fn with_function(mut self, name: &str, f: BuiltinFn, meta: FunctionMetadata) -> Self {
+        self.register_fn(name, f, meta);
+        self
+    }
+
+    /// In-place counterpart to `with_function`, for callers that hold a
+    /// `&mut Compiler` rather than the owned builder chain. Overwrites any
+    /// prior registration under `name`.
+    pub fn register_fn(&mut self, name: &str, f: BuiltinFn, meta: FunctionMetadata) {
+        self.custom_funcs.retain(|(existing, _, _)| existing != name);
+        self.custom_funcs.push((name.to_string(), f, meta));
+    }
+
+    /// Builds a `BytecodeCompiler` seeded with whatever config has been
+    /// registered on `self` (via `with_max_depth`/`with_function`/
+    /// `register_fn` and friends), for `compile*` to compile a single AST
+    /// against.
+    fn new_bytecode_compiler(&self) -> BytecodeCompiler {
+        let mut compiler = BytecodeCompiler::new();
+        if let Some(max_depth) = self.max_depth {
+            compiler = compiler.with_max_depth(max_depth);
+        }
+        for (name, f, meta) in &self.custom_funcs {
+            compiler.register_fn(name, *f, *meta);
+        }
+        compiler
+    }
+
+    /// Parses `input` into its `Expr` syntax tree without compiling it, so
+    /// callers can cheaply inspect an expression — e.g. collect its
+    /// referenced variables or called functions via `Expr::free_variables`/
+    /// `Expr::used_functions` — before deciding whether (or how) to compile
+    /// it.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::Compiler;
+    ///
+    /// let compiler = Compiler::new();
+    /// let expr = compiler.parse("sin(x) + cos(y)").unwrap();
+    /// let vars: Vec<String> = expr.free_variables().into_iter().collect();
+    /// assert_eq!(vars, vec!["x".to_string(), "y".to_string()]);
+    /// ```
+    pub fn parse(&self, input: &str) -> Result<Expr, String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer).map_err(|e| e.to_string())?;
+        parser.parse().map_err(|e| e.to_string())
     }
 
     /// Compile a mathematical expression into optimized bytecode
     pub fn compile(&self, input: &str) -> Result<Program, String> {
         let lexer = Lexer::new(input);
-        let mut parser = Parser::new(lexer)?;
-        let ast = parser.parse()?;
-        
-        let compiler = BytecodeCompiler::new();
-        Ok(compiler.compile(ast))
+        let mut parser = Parser::new(lexer).map_err(|e| e.to_string())?;
+        let ast = parser.parse().map_err(|e| e.to_string())?;
+
+        let compiler = self.new_bytecode_compiler();
+        compiler.compile(ast)
     }
+
+    /// Compiles several expressions sharing one variable namespace into a
+    /// single program that returns one result per output, in order — e.g.
+    /// `compile_multi(&["d1", "d2", "price"])` for a pricer whose three
+    /// formulas all reference the same `sigma*sqrt(t)`/`exp(-r*t)` terms.
+    ///
+    /// Identical subexpressions (by structure, not just by source text) are
+    /// hash-consed across every output: the shared term is computed once on
+    /// its first occurrence and reused everywhere else, rather than
+    /// recomputed per output. See `Program::eval_multi`.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::Compiler;
+    ///
+    /// let compiler = Compiler::new();
+    /// let program = compiler.compile_multi(&["x * x", "x * x + 1"]).unwrap();
+    /// let mut ctx = program.create_context();
+    /// ctx.set_by_index(0, 3.0);
+    /// let results = program.eval_multi(&ctx).unwrap();
+    /// assert_eq!(results, vec![9.0, 10.0]);
+    /// ```
+    pub fn compile_multi(&self, outputs: &[&str]) -> Result<Program, String> {
+        let mut asts = Vec::with_capacity(outputs.len());
+        for input in outputs {
+            let lexer = Lexer::new(input);
+            let mut parser = Parser::new(lexer).map_err(|e| e.to_string())?;
+            asts.push(parser.parse().map_err(|e| e.to_string())?);
+        }
+        self.new_bytecode_compiler().compile_multi(asts)
+    }
+
+    /// Compile a "program unit": zero or more `name(params) = body` function
+    /// definitions separated by `;`, followed by a final expression to
+    /// evaluate. Definitions may call each other, including themselves.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::Compiler;
+    ///
+    /// let compiler = Compiler::new();
+    /// let program = compiler.compile_unit("square(x) = x ^ 2; square(3) + 1").unwrap();
+    /// let result = program.eval(&program.create_context()).unwrap();
+    /// assert_eq!(result, 10.0);
+    /// ```
+    pub fn compile_unit(&self, input: &str) -> Result<Program, String> {
+        let tokens = lexer::lex(input).map_err(|e| e.to_string())?;
+        let segments = split_top_level_segments(&tokens);
+        if segments.is_empty() {
+            return Err("Empty program unit".to_string());
+        }
+
+        let (def_segments, main_segment) = segments.split_at(segments.len() - 1);
+
+        let mut defs = Vec::with_capacity(def_segments.len());
+        for &(start, end) in def_segments {
+            defs.push(parse_function_def(&input[start..end])?);
+        }
+
+        let (start, end) = main_segment[0];
+        let lexer = Lexer::new(&input[start..end]);
+        let mut parser = Parser::new(lexer).map_err(|e| e.to_string())?;
+        let main_ast = parser.parse().map_err(|e| e.to_string())?;
+
+        self.new_bytecode_compiler().compile_program(defs, main_ast)
+    }
+
+    /// Compiles a mathematical expression into a register-based program —
+    /// an alternative to the stack-machine `Program`/`compile`, whose
+    /// instructions index a flat register file instead of an implicit
+    /// stack, removing the per-operation push/pop from the hot loop.
+    ///
+    /// Straight-line expressions only: ternary `?:`, `&&`/`||` (which
+    /// `compile` lowers to short-circuiting jumps) and calls to
+    /// user-defined functions aren't supported, since the register format
+    /// has no encoding for branches or recursive calls. Use `compile` or
+    /// `compile_unit` for those.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::Compiler;
+    ///
+    /// let compiler = Compiler::new();
+    /// let program = compiler.compile_registers("x * x + 1").unwrap();
+    /// let mut ctx = program.create_context();
+    /// ctx.set_by_index(0, 3.0);
+    /// assert_eq!(program.eval(&ctx).unwrap(), 10.0);
+    /// ```
+    pub fn compile_registers(&self, input: &str) -> Result<RegisterProgram, String> {
+        let lexer = Lexer::new(input);
+        let mut parser = Parser::new(lexer).map_err(|e| e.to_string())?;
+        let ast = parser.parse().map_err(|e| e.to_string())?;
+
+        let compiler = self.new_bytecode_compiler();
+        compiler.compile_registers(ast)
+    }
+}
+
+/// Splits `tokens` (as produced by `lexer::lex`, `Token::EOF`-terminated)
+/// into `(start_offset, end_offset)` byte ranges on top-level
+/// `Token::Semicolon` boundaries. Operating on tokens rather than raw bytes
+/// means a `;` (or `=`, `(`, `)`, ...) embedded inside a string literal —
+/// which the lexer folds into a single `Token::Str`, not a standalone
+/// punctuation token — can't desync the split the way scanning the source
+/// text directly would.
+fn split_top_level_segments(tokens: &[(Token, Span)]) -> Vec<(usize, usize)> {
+    let mut segments = Vec::new();
+    let mut current_start: Option<usize> = None;
+    let mut current_end = 0usize;
+
+    for (token, span) in tokens {
+        match token {
+            Token::EOF => break,
+            Token::Semicolon => {
+                if let Some(start) = current_start.take() {
+                    segments.push((start, current_end));
+                }
+            }
+            _ => {
+                current_start.get_or_insert(span.start.offset);
+                current_end = span.end.offset;
+            }
+        }
+    }
+    if let Some(start) = current_start {
+        segments.push((start, current_end));
+    }
+    segments
+}
+
+/// Parses a `name(p1, p2, ...) = body` function definition segment.
+fn parse_function_def(segment: &str) -> Result<(String, Vec<String>, ast::Expr), String> {
+    let tokens = lexer::lex(segment).map_err(|e| e.to_string())?;
+    let assign_idx = tokens
+        .iter()
+        .position(|(token, _)| *token == Token::Assign)
+        .ok_or_else(|| format!("Expected function definition '=' in: {}", segment))?;
+
+    let (name, params) = parse_function_header(&tokens[..assign_idx], segment)?;
+
+    let body = &segment[tokens[assign_idx].1.end.offset..];
+    let lexer = Lexer::new(body);
+    let mut parser = Parser::new(lexer).map_err(|e| e.to_string())?;
+    let body_ast = parser.parse().map_err(|e| e.to_string())?;
+
+    Ok((name, params, body_ast))
+}
+
+/// Parses a function definition's `name(p1, p2, ...)` header from its
+/// already-lexed tokens (the slice before the `=`), reporting errors against
+/// `segment` (the original text) for context.
+fn parse_function_header(header: &[(Token, Span)], segment: &str) -> Result<(String, Vec<String>), String> {
+    let mut tokens = header.iter().map(|(token, _)| token);
+
+    let name = match tokens.next() {
+        Some(Token::Identifier(name)) => name.clone(),
+        _ => return Err(format!("Missing function name in definition: {}", segment)),
+    };
+    if tokens.next() != Some(&Token::LParen) {
+        return Err(format!("Expected '(' in function definition: {}", segment));
+    }
+
+    let mut params = Vec::new();
+    loop {
+        match tokens.next() {
+            Some(Token::RParen) => break,
+            Some(Token::Identifier(param)) => {
+                params.push(param.clone());
+                match tokens.next() {
+                    Some(Token::Comma) => continue,
+                    Some(Token::RParen) => break,
+                    _ => return Err(format!("Expected ',' or ')' in function definition: {}", segment)),
+                }
+            }
+            _ => return Err(format!("Expected ')' in function definition: {}", segment)),
+        }
+    }
+    if tokens.next().is_some() {
+        return Err(format!("Unexpected tokens after ')' in function definition: {}", segment));
+    }
+
+    Ok((name, params))
 }
 
 impl Default for Compiler {
@@ -46,40 +313,288 @@ impl Program {
         vm.run(context)
     }
 
-    /// Batch evaluation: evaluate with multiple variable sets efficiently
-    /// 
+    /// Like `eval`, but gives up with an `"operation budget exceeded"` error
+    /// once more than `max_steps` instructions have been dispatched, rather
+    /// than letting a pathological expression (e.g. deeply nested `Pow`)
+    /// from an untrusted source run unbounded.
+    pub fn eval_with_limit(&self, context: &Context, max_steps: u64) -> Result<f64, String> {
+        let mut vm = VM::new(self).with_limit(max_steps);
+        vm.run(context)
+    }
+
+    /// Like `eval_batch`, capped the same way `eval_with_limit` caps `eval`
+    /// — the budget is shared across the whole batch, not reset per row.
+    pub fn eval_batch_with_limit(
+        &self,
+        inputs: &[&[f64]],
+        out: &mut [f64],
+        max_steps: u64,
+    ) -> Result<(), String> {
+        let mut vm = VM::new(self).with_limit(max_steps);
+        vm.run_batch(inputs, out)
+    }
+
+    /// Batch/columnar evaluation: evaluate with many variable sets efficiently
+    ///
     /// This is significantly faster than calling `eval()` in a loop because:
-    /// - Reuses the same VM instance
-    /// - Avoids repeated context creation
+    /// - Decodes the instruction stream once, instead of once per row
+    /// - Reuses the same VM instance and scratch stack
     /// - Better cache locality
-    /// 
+    ///
     /// # Arguments
-    /// * `var_sets` - Slice of variable value slices. Each inner slice must contain
-    ///                values in the same order as `program.var_names`
-    /// 
-    /// # Returns
-    /// Vector of results, one for each variable set
-    /// 
+    /// * `inputs` - `inputs[v]` is the column of values for variable index `v`
+    ///              (matching `program.var_names`) across all rows. Every column
+    ///              must have length equal to `out.len()`.
+    /// * `out` - receives one result per row
+    ///
     /// # Example
     /// ```
     /// use matheval_core::Compiler;
-    /// 
+    ///
     /// let compiler = Compiler::new();
     /// let program = compiler.compile("x * 2 + y").unwrap();
-    /// 
-    /// // Batch evaluate with 3 different variable sets
-    /// let var_sets: Vec<&[f64]> = vec![
-    ///     &[1.0, 2.0],  // x=1, y=2 -> result: 4
-    ///     &[3.0, 4.0],  // x=3, y=4 -> result: 10
-    ///     &[5.0, 6.0],  // x=5, y=6 -> result: 16
-    /// ];
-    /// 
-    /// let results = program.eval_batch(&var_sets).unwrap();
-    /// assert_eq!(results, vec![4.0, 10.0, 16.0]);
+    ///
+    /// // Batch evaluate 3 rows: x = [1, 3, 5], y = [2, 4, 6]
+    /// let xs = [1.0, 3.0, 5.0];
+    /// let ys = [2.0, 4.0, 6.0];
+    /// let mut out = [0.0; 3];
+    /// program.eval_batch(&[&xs, &ys], &mut out).unwrap();
+    /// assert_eq!(out, [4.0, 10.0, 16.0]);
     /// ```
-    pub fn eval_batch(&self, var_sets: &[&[f64]]) -> Result<Vec<f64>, String> {
+    pub fn eval_batch(&self, inputs: &[&[f64]], out: &mut [f64]) -> Result<(), String> {
         let mut vm = VM::new(self);
-        vm.run_batch(var_sets)
+        vm.run_batch(inputs, out)
+    }
+
+    /// Evaluates `var_sets` across a small pool of OS threads, one `VM` per
+    /// thread, and returns the results in the same order as `var_sets`.
+    ///
+    /// Unlike `eval_batch`'s columnar `inputs` (one slice per *variable*,
+    /// shared across all rows), `var_sets[i]` here is one complete,
+    /// already-assembled row of variable values, positional against
+    /// `var_names` the same way `Context::set_by_index` is — the natural
+    /// shape for a Monte Carlo caller that has `n` independent paths and
+    /// wants them spread across cores rather than evaluated one at a time.
+    ///
+    /// This tree has no workspace manifest to add an optional `rayon`
+    /// dependency to, so this uses `std::thread::scope` directly instead of
+    /// gating on an unavailable feature — a small, dependency-free way to
+    /// get the same "one VM per thread, split the work, join in order"
+    /// shape. For a batch too small to be worth spreading across threads,
+    /// this falls back to evaluating sequentially, just like `eval_batch`.
+    ///
+    /// Each thread evaluates its rows one at a time with `eval`; processing
+    /// several rows per instruction dispatch (SIMD lanes) is what
+    /// `eval_batch` does for programs without jumps or user-function calls.
+    pub fn eval_batch_parallel(&self, var_sets: &[&[f64]]) -> Result<Vec<f64>, String> {
+        let expected = self.var_names.len();
+        for (i, row) in var_sets.iter().enumerate() {
+            if row.len() != expected {
+                return Err(format!(
+                    "var_sets[{}] has {} values, expected {} (matching var_names)",
+                    i,
+                    row.len(),
+                    expected
+                ));
+            }
+        }
+
+        let thread_count = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(var_sets.len().max(1));
+
+        if thread_count <= 1 || var_sets.len() < thread_count * 2 {
+            return var_sets.iter().map(|row| self.eval_row(row, expected)).collect();
+        }
+
+        let chunk_size = var_sets.len().div_ceil(thread_count);
+        std::thread::scope(|scope| {
+            let handles: Vec<_> = var_sets
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || -> Result<Vec<f64>, String> {
+                        chunk.iter().map(|row| self.eval_row(row, expected)).collect()
+                    })
+                })
+                .collect();
+
+            let mut merged = Vec::with_capacity(var_sets.len());
+            for handle in handles {
+                let chunk_result = handle.join().map_err(|_| "worker thread panicked".to_string())??;
+                merged.extend(chunk_result);
+            }
+            Ok(merged)
+        })
+    }
+
+    /// Binds `row` positionally into a fresh `Context` and evaluates it;
+    /// shared by `eval_batch_parallel`'s sequential fallback and its
+    /// per-thread chunks.
+    fn eval_row(&self, row: &[f64], expected: usize) -> Result<f64, String> {
+        let mut ctx = Context::with_capacity(expected);
+        for (i, &v) in row.iter().enumerate() {
+            ctx.set_by_index(i, v);
+        }
+        self.eval(&ctx)
+    }
+
+    /// Evaluate the program and its gradient in one pass: returns
+    /// `(value, d_value_d_var)` where the gradient vector is indexed the
+    /// same way as `var_names` (and `Context::set_by_index`).
+    ///
+    /// Uses reverse-mode (adjoint) automatic differentiation over the
+    /// bytecode, so it costs roughly one forward pass plus one backward
+    /// pass — exact derivatives, not a finite-difference approximation.
+    /// Useful for pricing/risk code that wants e.g. delta and vega from a
+    /// compiled pricing formula without hand-deriving them.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::{Compiler, Context};
+    ///
+    /// let compiler = Compiler::new();
+    /// let program = compiler.compile("x * x").unwrap();
+    ///
+    /// let mut ctx = Context::new();
+    /// ctx.set_by_index(0, 3.0);
+    /// let (value, grad) = program.eval_grad(&ctx).unwrap();
+    /// assert_eq!(value, 9.0);
+    /// assert_eq!(grad[0], 6.0); // d/dx(x^2) = 2x = 6
+    /// ```
+    pub fn eval_grad(&self, context: &Context) -> Result<(f64, Vec<f64>), String> {
+        let mut vm = VM::new(self);
+        vm.run_grad(context)
+    }
+
+    /// Runs the program `n` times against a deterministic PCG random stream
+    /// seeded from `seed`, returning the mean, variance, and standard error
+    /// of the results. `rand()`/`randn()` calls inside the program draw
+    /// fresh values from that stream on every run, so this is a first-class
+    /// Monte Carlo primitive: `max(S*exp(drift + vol*randn()) - K, 0)*discount`
+    /// gets you a priced expectation directly from the engine, without
+    /// hand-rolling an RNG and looping `eval()` outside it.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::{Compiler, Context};
+    ///
+    /// let compiler = Compiler::new();
+    /// let program = compiler.compile("randn()").unwrap();
+    /// let stats = program.simulate(&Context::new(), 20_000, 42).unwrap();
+    /// assert!(stats.mean.abs() < 0.05);
+    /// assert!((stats.variance - 1.0).abs() < 0.2);
+    /// ```
+    pub fn simulate(&self, context: &Context, n: usize, seed: u64) -> Result<SimStats, String> {
+        let mut vm = VM::new(self);
+        vm.run_simulate(context, n, seed)
+    }
+
+    /// Evaluates a program compiled by `Compiler::compile_multi`, returning
+    /// one result per output expression, in the order they were passed to
+    /// `compile_multi`.
+    pub fn eval_multi(&self, context: &Context) -> Result<Vec<f64>, String> {
+        let mut vm = VM::new(self);
+        vm.run_multi(context)
+    }
+
+    /// Finds the value of the variable at `var_index` that makes `eval()`
+    /// equal `target`, starting from `guess` — e.g. implied volatility:
+    /// solve a Black-Scholes `price(sigma)` formula for the `sigma` that
+    /// reproduces an observed market price.
+    ///
+    /// Uses Newton-Raphson, reusing `eval_grad`'s exact derivative for
+    /// `var_index` so each iteration is one combined forward/backward pass:
+    /// `x ← x − (f(x) − target) / f'(x)`, stopping once `|f(x) − target| <
+    /// 1e-10` or after `max_iterations`. If the derivative is near zero or a
+    /// step would leave `bracket`, falls back to one bisection step over
+    /// `bracket` instead, so a flat or non-monotonic region can't send
+    /// Newton's method off to infinity. Returns an error before iterating if
+    /// `target` isn't bracketed (`f(bracket.0)` and `f(bracket.1)` don't
+    /// straddle it), whether or not a fallback would be needed, or if the
+    /// iteration budget runs out without converging.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::{Compiler, Context};
+    ///
+    /// let compiler = Compiler::new();
+    /// let program = compiler.compile("x * x").unwrap();
+    /// let ctx = Context::new();
+    /// // Solve x^2 = 16 starting from a guess of 1, bracketed in [0, 10]
+    /// let root = program.solve_for(&ctx, 0, 16.0, 1.0, (0.0, 10.0)).unwrap();
+    /// assert!((root - 4.0).abs() < 1e-6);
+    /// ```
+    pub fn solve_for(
+        &self,
+        context: &Context,
+        var_index: usize,
+        target: f64,
+        guess: f64,
+        bracket: (f64, f64),
+    ) -> Result<f64, String> {
+        const MAX_ITERATIONS: u32 = 100;
+        const TOLERANCE: f64 = 1e-10;
+
+        let mut ctx = context.clone();
+        let mut x = guess;
+
+        let (mut lo, mut hi) = bracket;
+        ctx.set_by_index(var_index, lo);
+        let mut f_lo = self.eval(&ctx)? - target;
+        ctx.set_by_index(var_index, hi);
+        let f_hi = self.eval(&ctx)? - target;
+        if f_lo == 0.0 {
+            return Ok(lo);
+        }
+        if f_hi == 0.0 {
+            return Ok(hi);
+        }
+        if f_lo.signum() == f_hi.signum() {
+            return Err(format!(
+                "target {} is not bracketed by [{}, {}]",
+                target, bracket.0, bracket.1
+            ));
+        }
+
+        for _ in 0..MAX_ITERATIONS {
+            ctx.set_by_index(var_index, x);
+            let (value, grad) = self.eval_grad(&ctx)?;
+            let residual = value - target;
+            if residual.abs() < TOLERANCE {
+                return Ok(x);
+            }
+
+            let derivative = grad[var_index];
+            let newton_step = if derivative.abs() > f64::EPSILON {
+                Some(x - residual / derivative)
+            } else {
+                None
+            };
+
+            x = match newton_step {
+                Some(next) if next.is_finite() && next >= lo && next <= hi => next,
+                _ => {
+                    // Derivative too flat or the Newton step left the
+                    // bracket: fall back to one bisection step instead.
+                    let mid = (lo + hi) / 2.0;
+                    ctx.set_by_index(var_index, mid);
+                    let f_mid = self.eval(&ctx)? - target;
+                    if f_mid.signum() == f_lo.signum() {
+                        lo = mid;
+                        f_lo = f_mid;
+                    } else {
+                        hi = mid;
+                    }
+                    mid
+                }
+            };
+        }
+
+        Err(format!(
+            "solve_for did not converge within {} iterations",
+            MAX_ITERATIONS
+        ))
     }
 
     /// Create a context pre-sized for this program
@@ -148,6 +663,69 @@ mod tests {
         assert_eq!(result, 3.0 + 4.0);
     }
 
+    fn clamp(args: &[f64]) -> f64 {
+        args[0].max(args[1]).min(args[2])
+    }
+
+    #[test]
+    fn test_with_function_registers_through_the_public_compiler() {
+        let program = Compiler::new()
+            .with_function("clamp", clamp, FunctionMetadata::fixed(3))
+            .compile("clamp(x, 0, 1)")
+            .unwrap();
+
+        let mut context = program.create_context();
+        context.set_by_index(0, 5.0);
+        assert_eq!(program.eval(&context).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_register_fn_in_place_on_the_public_compiler() {
+        let mut compiler = Compiler::new();
+        compiler.register_fn("clamp", clamp, FunctionMetadata::fixed(3));
+
+        let program = compiler.compile("clamp(x, 0, 1)").unwrap();
+        let mut context = program.create_context();
+        context.set_by_index(0, 5.0);
+        assert_eq!(program.eval(&context).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_parse_exposes_expr_inspection_to_external_callers() {
+        let compiler = Compiler::new();
+        let expr = compiler.parse("sin(x) + cos(y) * x").unwrap();
+
+        let vars: Vec<String> = expr.free_variables().into_iter().collect();
+        assert_eq!(vars, vec!["x".to_string(), "y".to_string()]);
+
+        let funcs: Vec<String> = expr.used_functions().into_iter().collect();
+        assert_eq!(funcs, vec!["cos".to_string(), "sin".to_string()]);
+    }
+
+    #[test]
+    fn test_with_max_depth_tightens_the_limit_through_the_public_compiler() {
+        let expr = "-".repeat(10) + "1";
+
+        // 10 levels compiles fine under the default cap...
+        assert!(Compiler::new().compile(&expr).is_ok());
+
+        // ...but is rejected once max_depth is tightened below it.
+        let result = Compiler::new().with_max_depth(5).compile(&expr);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("nesting depth"));
+    }
+
+    #[test]
+    fn test_with_function_also_applies_to_compile_unit() {
+        let program = Compiler::new()
+            .with_function("clamp", clamp, FunctionMetadata::fixed(3))
+            .compile_unit("scaled(x) = clamp(x, 0, 1); scaled(5)")
+            .unwrap();
+
+        let result = program.eval(&program.create_context()).unwrap();
+        assert_eq!(result, 1.0);
+    }
+
     #[test]
     fn test_math_functions() {
         let compiler = Compiler::new();
@@ -262,54 +840,207 @@ mod tests {
     fn test_eval_batch_basic() {
         let compiler = Compiler::new();
         let program = compiler.compile("x * 2 + y").unwrap();
-        
-        let var_sets: Vec<&[f64]> = vec![
-            &[1.0, 2.0],  // x=1, y=2 -> 1*2+2 = 4
-            &[3.0, 4.0],  // x=3, y=4 -> 3*2+4 = 10
-            &[5.0, 6.0],  // x=5, y=6 -> 5*2+6 = 16
-        ];
-        
-        let results = program.eval_batch(&var_sets).unwrap();
-        assert_eq!(results, vec![4.0, 10.0, 16.0]);
+
+        let xs = [1.0, 3.0, 5.0];
+        let ys = [2.0, 4.0, 6.0];
+        let mut out = [0.0; 3];
+
+        program.eval_batch(&[&xs, &ys], &mut out).unwrap();
+        assert_eq!(out, [4.0, 10.0, 16.0]);
     }
 
     #[test]
     fn test_eval_batch_with_functions() {
         let compiler = Compiler::new();
         let program = compiler.compile("sin(x) + cos(y)").unwrap();
-        
-        let var_sets: Vec<&[f64]> = vec![
-            &[0.0, 0.0],
-            &[std::f64::consts::PI / 2.0, 0.0],
-        ];
-        
-        let results = program.eval_batch(&var_sets).unwrap();
-        assert!((results[0] - 1.0).abs() < 1e-10);  // sin(0) + cos(0) = 1
-        assert!((results[1] - 2.0).abs() < 1e-6);   // sin(Ï€/2) + cos(0) = 1 + 1 = 2
+
+        let xs = [0.0, std::f64::consts::PI / 2.0];
+        let ys = [0.0, 0.0];
+        let mut out = [0.0; 2];
+
+        program.eval_batch(&[&xs, &ys], &mut out).unwrap();
+        assert!((out[0] - 1.0).abs() < 1e-10); // sin(0) + cos(0) = 1
+        assert!((out[1] - 2.0).abs() < 1e-6); // sin(pi/2) + cos(0) = 1 + 1 = 2
     }
 
     #[test]
     fn test_eval_batch_empty() {
         let compiler = Compiler::new();
         let program = compiler.compile("x + y").unwrap();
-        
-        let var_sets: Vec<&[f64]> = vec![];
-        let results = program.eval_batch(&var_sets).unwrap();
-        assert_eq!(results.len(), 0);
+
+        let xs: [f64; 0] = [];
+        let ys: [f64; 0] = [];
+        let mut out: [f64; 0] = [];
+        program.eval_batch(&[&xs, &ys], &mut out).unwrap();
+        assert_eq!(out.len(), 0);
+    }
+
+    #[test]
+    fn test_eval_batch_ragged_final_chunk_matches_scalar_eval() {
+        // 7 rows doesn't divide evenly into SIMD_LANES (4), exercising the
+        // padded final chunk.
+        let compiler = Compiler::new();
+        let program = compiler.compile("x * x + 1").unwrap();
+
+        let xs: Vec<f64> = (1..=7).map(|i| i as f64).collect();
+        let mut out = vec![0.0; 7];
+        program.eval_batch(&[&xs], &mut out).unwrap();
+
+        for (i, &x) in xs.iter().enumerate() {
+            let mut ctx = program.create_context();
+            ctx.set_by_index(0, x);
+            assert_eq!(out[i], program.eval(&ctx).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_eval_batch_reports_division_by_zero_row() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("10 / x").unwrap();
+
+        let xs = [1.0, 2.0, 0.0, 4.0];
+        let mut out = [0.0; 4];
+        let result = program.eval_batch(&[&xs], &mut out);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("row 2"));
+    }
+
+    #[test]
+    fn test_eval_batch_ternary_falls_back_to_scalar_path_and_is_correct() {
+        // Contains a Jump/JumpIfFalse, so eval_batch can't take the SIMD
+        // lane path and must fall back to evaluating row by row.
+        let compiler = Compiler::new();
+        let program = compiler.compile("x > 0 ? sqrt(x) : -x").unwrap();
+
+        let xs = [4.0, -4.0, 9.0, -9.0, 16.0];
+        let mut out = [0.0; 5];
+        program.eval_batch(&[&xs], &mut out).unwrap();
+        assert_eq!(out, [2.0, 4.0, 3.0, 9.0, 4.0]);
     }
 
     #[test]
     fn test_eval_batch_wrong_var_count() {
         let compiler = Compiler::new();
-        let program = compiler.compile("x + y").unwrap();  // Expects 2 variables
-        
-        let var_sets: Vec<&[f64]> = vec![
-            &[1.0],  // Only 1 variable - should error
-        ];
-        
-        let result = program.eval_batch(&var_sets);
+        let program = compiler.compile("x + y").unwrap(); // Expects 2 variables
+
+        let xs = [1.0];
+        let mut out = [0.0];
+
+        let result = program.eval_batch(&[&xs], &mut out); // Only 1 variable - should error
         assert!(result.is_err());
-        assert!(result.unwrap_err().contains("expected 2"));
+        assert!(result.unwrap_err().contains("Expected 2"));
+    }
+
+    #[test]
+    fn test_comparison_and_boolean_operators() {
+        let compiler = Compiler::new();
+        let ctx = Context::new();
+
+        assert_eq!(compiler.compile("5 > 3").unwrap().eval(&ctx).unwrap(), 1.0);
+        assert_eq!(compiler.compile("5 < 3").unwrap().eval(&ctx).unwrap(), 0.0);
+        assert_eq!(compiler.compile("3 == 3").unwrap().eval(&ctx).unwrap(), 1.0);
+        assert_eq!(compiler.compile("3 != 3").unwrap().eval(&ctx).unwrap(), 0.0);
+        assert_eq!(compiler.compile("1 && 1").unwrap().eval(&ctx).unwrap(), 1.0);
+        assert_eq!(compiler.compile("1 && 0").unwrap().eval(&ctx).unwrap(), 0.0);
+        assert_eq!(compiler.compile("0 || 1").unwrap().eval(&ctx).unwrap(), 1.0);
+        assert_eq!(compiler.compile("!0").unwrap().eval(&ctx).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_ternary_conditional() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("x > 0 ? sqrt(x) : -x").unwrap();
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 4.0);
+        assert_eq!(program.eval(&ctx).unwrap(), 2.0);
+
+        ctx.set_by_index(0, -4.0);
+        assert_eq!(program.eval(&ctx).unwrap(), 4.0);
+    }
+
+    #[test]
+    fn test_and_or_short_circuit_avoids_evaluating_rhs() {
+        let compiler = Compiler::new();
+        let ctx = Context::new();
+
+        // 1/0 would error if evaluated, so short-circuiting must skip it.
+        assert_eq!(compiler.compile("0 && (1 / 0)").unwrap().eval(&ctx).unwrap(), 0.0);
+        assert_eq!(compiler.compile("1 || (1 / 0)").unwrap().eval(&ctx).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_user_defined_function() {
+        let compiler = Compiler::new();
+        let program = compiler.compile_unit("square(x) = x ^ 2; square(3) + 1").unwrap();
+        let ctx = program.create_context();
+        assert_eq!(program.eval(&ctx).unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_user_defined_function_multiple_args() {
+        let compiler = Compiler::new();
+        let program = compiler
+            .compile_unit("hypot2(a, b) = a ^ 2 + b ^ 2; hypot2(3, 4)")
+            .unwrap();
+        let ctx = program.create_context();
+        assert_eq!(program.eval(&ctx).unwrap(), 25.0);
+    }
+
+    #[test]
+    fn test_user_defined_function_recursion() {
+        let compiler = Compiler::new();
+        let program = compiler
+            .compile_unit("fact(n) = n <= 1 ? 1 : n * fact(n - 1); fact(5)")
+            .unwrap();
+        let ctx = program.create_context();
+        assert_eq!(program.eval(&ctx).unwrap(), 120.0);
+    }
+
+    #[test]
+    fn test_user_defined_function_mutual_recursion() {
+        let compiler = Compiler::new();
+        let program = compiler
+            .compile_unit(
+                "is_even(n) = n == 0 ? 1 : is_odd(n - 1); is_odd(n) = n == 0 ? 0 : is_even(n - 1); is_even(10)",
+            )
+            .unwrap();
+        let ctx = program.create_context();
+        assert_eq!(program.eval(&ctx).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_user_defined_function_wrong_arity() {
+        let compiler = Compiler::new();
+        let result = compiler.compile_unit("square(x) = x ^ 2; square(1, 2)");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("expects 1 argument"));
+    }
+
+    #[test]
+    fn test_user_defined_function_unbounded_recursion_errors() {
+        let compiler = Compiler::new();
+        let program = compiler.compile_unit("loop(n) = loop(n + 1); loop(0)").unwrap();
+        let ctx = program.create_context();
+        let result = program.eval(&ctx);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Recursion limit exceeded"));
+    }
+
+    #[test]
+    fn test_compile_unit_string_literal_containing_a_semicolon_and_equals() {
+        // A `;` or bare `=` inside a string literal must not be mistaken for
+        // a segment separator or a definition's `=`, since both used to be
+        // found by scanning raw source bytes before lexing. The string
+        // itself still can't be compiled to bytecode yet (the VM stack is
+        // f64-only), so the error should be that unrelated, well-formed
+        // limitation rather than a bogus "Unterminated string literal" or
+        // mismatched-paren error from splitting mid-literal.
+        let compiler = Compiler::new();
+        let err = compiler
+            .compile_unit(r#"f(x) = "a;b" == "a;b" ? x : 0; f(5)"#)
+            .unwrap_err();
+        assert!(err.contains("String literals are not yet supported"));
     }
 
     #[test]
@@ -317,32 +1048,226 @@ mod tests {
         // Simulate simplified option pricing
         let compiler = Compiler::new();
         let program = compiler.compile("max(S - K, 0) * discount").unwrap();
-        
+
         // Variables are ordered by first appearance: S, K, discount
-        
-        // Prepare parameters
-        let k = 105.0;
-        let discount = 0.95;
-        
-        // Different stock prices
-        let s_values = vec![90.0, 100.0, 110.0, 120.0, 130.0];
-        let var_sets: Vec<Vec<f64>> = s_values.iter().map(|&s| {
-            vec![s, k, discount]  // S, K, discount (order of first appearance)
-        }).collect();
-        let var_sets_refs: Vec<&[f64]> = var_sets.iter().map(|v| v.as_slice()).collect();
-        
-        let results = program.eval_batch(&var_sets_refs).unwrap();
-        
+        let s_values = [90.0, 100.0, 110.0, 120.0, 130.0];
+        let k_values = [105.0; 5];
+        let discount_values = [0.95; 5];
+        let mut out = [0.0; 5];
+
+        program
+            .eval_batch(&[&s_values, &k_values, &discount_values], &mut out)
+            .unwrap();
+
         // All results should be non-negative (option payoff)
-        for result in &results {
+        for result in &out {
             assert!(*result >= 0.0);
         }
-        
+
         // Results: max(S-K, 0) * discount for each S value
-        assert_eq!(results[0], 0.0);     // max(90-105, 0) * 0.95 = 0
-        assert_eq!(results[1], 0.0);     // max(100-105, 0) * 0.95 = 0  
-        assert!((results[2] - 4.75).abs() < 0.01);  // max(110-105, 0) * 0.95 = 4.75
-        assert!((results[3] - 14.25).abs() < 0.01); // max(120-105, 0) * 0.95 = 14.25
-        assert!((results[4] - 23.75).abs() < 0.01); // max(130-105, 0) * 0.95 = 23.75
+        assert_eq!(out[0], 0.0); // max(90-105, 0) * 0.95 = 0
+        assert_eq!(out[1], 0.0); // max(100-105, 0) * 0.95 = 0
+        assert!((out[2] - 4.75).abs() < 0.01); // max(110-105, 0) * 0.95 = 4.75
+        assert!((out[3] - 14.25).abs() < 0.01); // max(120-105, 0) * 0.95 = 14.25
+        assert!((out[4] - 23.75).abs() < 0.01); // max(130-105, 0) * 0.95 = 23.75
+    }
+
+    #[test]
+    fn test_eval_batch_parallel_matches_sequential_eval() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("max(x - k, 0) * discount").unwrap();
+
+        let rows: Vec<[f64; 3]> = (0..500)
+            .map(|i| [80.0 + i as f64, 105.0, 0.95])
+            .collect();
+        let row_refs: Vec<&[f64]> = rows.iter().map(|r| r.as_slice()).collect();
+
+        let parallel = program.eval_batch_parallel(&row_refs).unwrap();
+
+        let mut ctx = program.create_context();
+        for (i, row) in rows.iter().enumerate() {
+            ctx.set_by_index(0, row[0]);
+            ctx.set_by_index(1, row[1]);
+            ctx.set_by_index(2, row[2]);
+            assert_eq!(parallel[i], program.eval(&ctx).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_eval_batch_parallel_rejects_mismatched_var_set_length() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("x + y").unwrap();
+        let bad_row = [1.0];
+        let result = program.eval_batch_parallel(&[&bad_row]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_solve_for_finds_root_of_monotonic_function() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("x * x").unwrap();
+        let ctx = Context::new();
+        let root = program.solve_for(&ctx, 0, 16.0, 1.0, (0.0, 10.0)).unwrap();
+        assert!((root - 4.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_solve_for_implied_volatility_style_formula() {
+        // price(sigma) = sigma * s, monotonic in sigma, mimics an implied-vol
+        // style inversion of a pricing formula for one of its inputs.
+        let compiler = Compiler::new();
+        let program = compiler.compile("sigma * s").unwrap();
+        let mut ctx = program.create_context();
+        let s_idx = program.var_names.iter().position(|n| n == "s").unwrap();
+        let sigma_idx = program.var_names.iter().position(|n| n == "sigma").unwrap();
+        ctx.set_by_index(s_idx, 100.0);
+
+        let target_price = 20.0;
+        let implied_sigma = program
+            .solve_for(&ctx, sigma_idx, target_price, 0.1, (0.0001, 5.0))
+            .unwrap();
+        assert!((implied_sigma - 0.2).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_solve_for_falls_back_to_bisection_when_derivative_is_flat() {
+        // x^3 has a zero derivative at x=0, which would stall a pure Newton
+        // step started right there; the bisection fallback should still
+        // converge using the bracket.
+        let compiler = Compiler::new();
+        let program = compiler.compile("x * x * x").unwrap();
+        let ctx = Context::new();
+        let root = program.solve_for(&ctx, 0, 8.0, 0.0, (0.0, 10.0)).unwrap();
+        assert!((root - 2.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_solve_for_rejects_an_unbracketed_target() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("x * x").unwrap();
+        let ctx = Context::new();
+        let result = program.solve_for(&ctx, 0, 16.0, 1.0, (0.0, 2.0));
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_compile_registers_matches_stack_compile() {
+        let compiler = Compiler::new();
+        let stack_program = compiler.compile("(x + y) * (x - y) / 2").unwrap();
+        let register_program = Compiler::new().compile_registers("(x + y) * (x - y) / 2").unwrap();
+
+        let mut ctx = stack_program.create_context();
+        ctx.set_by_index(0, 7.0);
+        ctx.set_by_index(1, 3.0);
+
+        assert_eq!(
+            stack_program.eval(&ctx).unwrap(),
+            register_program.eval(&ctx).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_compile_registers_reuses_a_freed_register_for_a_chain() {
+        // `a + b + c + d` nets one live temporary at a time (each `+`
+        // consumes its two operands before the next is computed), so the
+        // allocator should never need more than 2 registers live at once.
+        let program = Compiler::new().compile_registers("a + b + c + d").unwrap();
+        assert_eq!(program.register_count, 2);
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 1.0);
+        ctx.set_by_index(1, 2.0);
+        ctx.set_by_index(2, 3.0);
+        ctx.set_by_index(3, 4.0);
+        assert_eq!(program.eval(&ctx).unwrap(), 10.0);
+    }
+
+    #[test]
+    fn test_compile_registers_handles_calls_with_contiguous_arguments() {
+        let program = Compiler::new().compile_registers("max(min(x, y), z)").unwrap();
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 1.0);
+        ctx.set_by_index(1, 2.0);
+        ctx.set_by_index(2, 1.5);
+        assert_eq!(program.eval(&ctx).unwrap(), 1.5);
+    }
+
+    #[test]
+    fn test_compile_registers_division_by_zero_is_an_error() {
+        let program = Compiler::new().compile_registers("1 / x").unwrap();
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 0.0);
+        assert!(program.eval(&ctx).unwrap_err().contains("Division by zero"));
+    }
+
+    #[test]
+    fn test_compile_registers_rejects_ternary() {
+        let result = Compiler::new().compile_registers("x > 0 ? 1 : -1");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_compile_registers_rejects_short_circuit_and() {
+        let result = Compiler::new().compile_registers("x && y");
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_compile_registers_rejects_user_defined_function_calls() {
+        let lexer = Lexer::new("square(y)");
+        let mut parser = Parser::new(lexer).unwrap();
+        let ast = parser.parse().unwrap();
+
+        let result = BytecodeCompiler::new()
+            .with_user_functions(&[("square".to_string(), 1)])
+            .compile_registers(ast);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_to_packed_matches_stack_compile() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("(x + y) * (x - y) / 2").unwrap();
+        let packed = program.to_packed().unwrap();
+
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 7.0);
+        ctx.set_by_index(1, 3.0);
+
+        assert_eq!(program.eval(&ctx).unwrap(), packed.eval(&ctx).unwrap());
+    }
+
+    #[test]
+    fn test_to_packed_handles_ternary_jumps() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("x > 0 ? x : -x").unwrap();
+        let packed = program.to_packed().unwrap();
+
+        let mut ctx = packed.create_context();
+        ctx.set_by_index(0, -5.0);
+        assert_eq!(packed.eval(&ctx).unwrap(), 5.0);
+
+        ctx.set_by_index(0, 5.0);
+        assert_eq!(packed.eval(&ctx).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_to_packed_handles_user_defined_function_recursion() {
+        let compiler = Compiler::new();
+        let program = compiler
+            .compile_unit("fact(n) = n <= 1 ? 1 : n * fact(n - 1); fact(5)")
+            .unwrap();
+        let packed = program.to_packed().unwrap();
+        let ctx = packed.create_context();
+        assert_eq!(packed.eval(&ctx).unwrap(), 120.0);
+    }
+
+    #[test]
+    fn test_to_packed_division_by_zero_is_an_error() {
+        let compiler = Compiler::new();
+        let program = compiler.compile("1 / x").unwrap();
+        let packed = program.to_packed().unwrap();
+        let mut ctx = packed.create_context();
+        ctx.set_by_index(0, 0.0);
+        assert!(packed.eval(&ctx).unwrap_err().contains("Division by zero"));
     }
 }
@@ -0,0 +1,254 @@
+//! Fixed-width packed instruction encoding, an alternative to `bytecode`'s
+//! variable-length `Vec<u8>` stream. Every instruction is one `u32` word:
+//! the low 8 bits hold the opcode, the next 16 bits hold a primary operand
+//! (a constant/variable/function/local index, or a jump's target), and the
+//! top 8 bits hold a secondary one (e.g. a call's argument count) — accessed
+//! through the `DecodeInstruction` trait rather than `bytecode`'s ad-hoc
+//! `read_u16`/`pc += 1` arithmetic. `pc` advances by exactly one word per
+//! step and jump targets are plain word indices, so decoding is branch-free
+//! bit-shifts with no mid-operand bytes to worry about.
+//!
+//! `Program::to_packed` converts an existing byte-stream program, so the two
+//! encodings can coexist behind a flag while callers migrate; this is the
+//! layout/trait approach used by the lunar_wave VM.
+
+use crate::bytecode::{BuiltinFn, OpCode, Program};
+use crate::vm::Context;
+
+const OPCODE_BITS: u32 = 8;
+const A_BITS: u32 = 16;
+const OPCODE_MASK: u32 = (1 << OPCODE_BITS) - 1;
+const A_MASK: u32 = (1 << A_BITS) - 1;
+
+/// Packs `opcode` into the low 8 bits, `a` into the next 16, and `b` into
+/// the top 8 — the inverse of `DecodeInstruction`'s accessors.
+pub(crate) fn pack(opcode: OpCode, a: u16, b: u8) -> u32 {
+    (opcode as u32) | ((a as u32) << OPCODE_BITS) | ((b as u32) << (OPCODE_BITS + A_BITS))
+}
+
+/// Bitfield accessors for a packed instruction word, implemented directly on
+/// `u32` so decoding a word is a handful of shifts with no intermediate
+/// struct.
+pub(crate) trait DecodeInstruction {
+    /// The instruction's opcode, decoded from the low 8 bits.
+    fn opcode(self) -> Option<OpCode>;
+    /// The 16-bit primary operand: a constant/variable/function/local index,
+    /// or a jump's target word index.
+    fn a(self) -> u16;
+    /// `a()` widened to `usize`, for indexing straight into a slice.
+    fn ax(self) -> usize;
+    /// The 8-bit secondary operand, e.g. a call's argument count.
+    fn b(self) -> u8;
+}
+
+impl DecodeInstruction for u32 {
+    fn opcode(self) -> Option<OpCode> {
+        OpCode::from_u8((self & OPCODE_MASK) as u8)
+    }
+
+    fn a(self) -> u16 {
+        ((self >> OPCODE_BITS) & A_MASK) as u16
+    }
+
+    fn ax(self) -> usize {
+        self.a() as usize
+    }
+
+    fn b(self) -> u8 {
+        (self >> (OPCODE_BITS + A_BITS)) as u8
+    }
+}
+
+/// A program compiled into the fixed-width packed encoding, produced by
+/// `Program::to_packed` and run with `PackedProgram::eval` (or directly via
+/// `VM::run_packed`).
+///
+/// The short byte-stream-only opcode forms (`LoadConstU8`, `CallU8`, ...)
+/// don't exist here: every operand fits in one word regardless of its
+/// value, so a packed instruction always uses its wide opcode
+/// (`OpCode::LoadConst`, `OpCode::Call`, ...) with the narrow forms folded
+/// into it by `from_program`.
+pub struct PackedProgram {
+    pub(crate) words: Vec<u32>,
+    pub(crate) constants: Vec<f64>,
+    pub(crate) func_table: Vec<BuiltinFn>,
+    /// User-defined function bodies, left as ordinary byte-stream `Program`s
+    /// — `OpCode::CallUser` dispatches into these via the regular `VM`
+    /// rather than a packed call, same scope boundary `RegisterProgram`
+    /// draws around recursive calls.
+    pub(crate) user_funcs: Vec<Program>,
+    pub(crate) local_count: usize,
+    pub(crate) max_call_depth: u32,
+    var_names: Vec<String>,
+}
+
+impl PackedProgram {
+    pub(crate) fn new(
+        words: Vec<u32>,
+        constants: Vec<f64>,
+        func_table: Vec<BuiltinFn>,
+        user_funcs: Vec<Program>,
+        local_count: usize,
+        max_call_depth: u32,
+        var_names: Vec<String>,
+    ) -> Self {
+        Self {
+            words,
+            constants,
+            func_table,
+            user_funcs,
+            local_count,
+            max_call_depth,
+            var_names,
+        }
+    }
+
+    /// Evaluate the program with the given context. Same contract as
+    /// `Program::eval`: errors on a missing context variable or a division
+    /// by zero.
+    pub fn eval(&self, context: &Context) -> Result<f64, String> {
+        crate::vm::VM::run_packed(self, context)
+    }
+
+    /// Create a context pre-sized for this program.
+    pub fn create_context(&self) -> Context {
+        Context::with_capacity(self.var_names.len())
+    }
+
+    pub(crate) fn var_names(&self) -> &[String] {
+        &self.var_names
+    }
+}
+
+/// Converts `program`'s byte-stream instructions into packed `u32` words,
+/// resolving each `Jump`/`JumpIfFalse`'s byte-offset target into a word
+/// index (mirroring `vm::decode_ops`'s two-pass offset-fixup approach) and
+/// folding every `*U8` short form into its wide opcode.
+pub(crate) fn from_program(program: &Program) -> Result<PackedProgram, String> {
+    let instructions = &program.instructions;
+    let mut words: Vec<u32> = Vec::new();
+    let mut offset_to_word = vec![usize::MAX; instructions.len() + 1];
+    let mut jump_fixups: Vec<(usize, usize)> = Vec::new();
+    let mut pc = 0usize;
+
+    while pc < instructions.len() {
+        offset_to_word[pc] = words.len();
+        let byte = instructions[pc];
+        let opcode = OpCode::from_u8(byte).ok_or_else(|| format!("Unknown opcode: {}", byte))?;
+        pc += 1;
+
+        let word = match opcode {
+            OpCode::LoadConst => {
+                let a = program.read_u16_at(pc);
+                pc += 2;
+                pack(OpCode::LoadConst, a, 0)
+            }
+            OpCode::LoadConstU8 => {
+                let a = instructions[pc] as u16;
+                pc += 1;
+                pack(OpCode::LoadConst, a, 0)
+            }
+            OpCode::LoadVar => {
+                let a = program.read_u16_at(pc);
+                pc += 2;
+                pack(OpCode::LoadVar, a, 0)
+            }
+            OpCode::LoadVarU8 => {
+                let a = instructions[pc] as u16;
+                pc += 1;
+                pack(OpCode::LoadVar, a, 0)
+            }
+            OpCode::Add
+            | OpCode::Sub
+            | OpCode::Mul
+            | OpCode::Div
+            | OpCode::Pow
+            | OpCode::Neg
+            | OpCode::Lt
+            | OpCode::Gt
+            | OpCode::Le
+            | OpCode::Ge
+            | OpCode::Eq
+            | OpCode::Ne
+            | OpCode::And
+            | OpCode::Or
+            | OpCode::Not
+            | OpCode::Rand
+            | OpCode::Randn
+            | OpCode::Dup
+            | OpCode::Swap
+            | OpCode::Pop => pack(opcode, 0, 0),
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                let target = program.read_u16_at(pc) as usize;
+                pc += 2;
+                jump_fixups.push((words.len(), target));
+                pack(opcode, 0, 0) // patched once every offset is known
+            }
+            OpCode::Call => {
+                let func_idx = program.read_u16_at(pc);
+                pc += 2;
+                let arg_count = instructions[pc];
+                pc += 1;
+                pack(OpCode::Call, func_idx, arg_count)
+            }
+            OpCode::CallU8 => {
+                let func_idx = instructions[pc] as u16;
+                pc += 1;
+                let arg_count = instructions[pc];
+                pc += 1;
+                pack(OpCode::Call, func_idx, arg_count)
+            }
+            OpCode::CallUser => {
+                let user_idx = program.read_u16_at(pc);
+                pc += 2;
+                let arg_count = instructions[pc];
+                pc += 1;
+                pack(OpCode::CallUser, user_idx, arg_count)
+            }
+            OpCode::StoreLocal => {
+                let a = program.read_u16_at(pc);
+                pc += 2;
+                pack(OpCode::StoreLocal, a, 0)
+            }
+            OpCode::StoreLocalU8 => {
+                let a = instructions[pc] as u16;
+                pc += 1;
+                pack(OpCode::StoreLocal, a, 0)
+            }
+            OpCode::LoadLocal => {
+                let a = program.read_u16_at(pc);
+                pc += 2;
+                pack(OpCode::LoadLocal, a, 0)
+            }
+            OpCode::LoadLocalU8 => {
+                let a = instructions[pc] as u16;
+                pc += 1;
+                pack(OpCode::LoadLocal, a, 0)
+            }
+        };
+        words.push(word);
+    }
+    offset_to_word[instructions.len()] = words.len();
+
+    for (word_index, byte_target) in jump_fixups {
+        let target_word = offset_to_word[byte_target];
+        if target_word == usize::MAX {
+            return Err(format!(
+                "Jump targets byte offset {}, which is not an instruction boundary",
+                byte_target
+            ));
+        }
+        let opcode = words[word_index].opcode().expect("just packed above");
+        words[word_index] = pack(opcode, target_word as u16, 0);
+    }
+
+    Ok(PackedProgram::new(
+        words,
+        program.constants.clone(),
+        program.func_table.clone(),
+        program.user_funcs.clone(),
+        program.local_count,
+        program.max_call_depth,
+        program.var_names.clone(),
+    ))
+}
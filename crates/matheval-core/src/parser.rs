@@ -1,36 +1,46 @@
-use crate::token::Token;
+use crate::ast::{BinaryOp, Expr, UnaryOp};
+use crate::error::{Error, Span};
 use crate::lexer::Lexer;
-use crate::ast::{Expr, BinaryOp, UnaryOp};
+use crate::token::Token;
 
 pub struct Parser<'a> {
     lexer: Lexer<'a>,
     current_token: Token,
+    current_span: Span,
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(mut lexer: Lexer<'a>) -> Result<Self, String> {
-        let current_token = lexer.next_token()?;
+    pub fn new(mut lexer: Lexer<'a>) -> Result<Self, Error> {
+        let (current_token, current_span) = lexer.next_token()?;
         Ok(Self {
             lexer,
             current_token,
+            current_span,
         })
     }
 
-    fn advance(&mut self) -> Result<(), String> {
-        self.current_token = self.lexer.next_token()?;
+    fn advance(&mut self) -> Result<(), Error> {
+        let (token, span) = self.lexer.next_token()?;
+        self.current_token = token;
+        self.current_span = span;
         Ok(())
     }
 
-    pub fn parse(&mut self) -> Result<Expr, String> {
+    fn error_here(&self, err: Error) -> Error {
+        err.with_position(self.current_span.start)
+            .with_source(self.lexer.source().to_string())
+    }
+
+    pub fn parse(&mut self) -> Result<Expr, Error> {
         self.expression(0)
     }
 
-    fn expression(&mut self, min_bp: u8) -> Result<Expr, String> {
+    fn expression(&mut self, min_bp: u8) -> Result<Expr, Error> {
         let mut left = self.nud()?;
 
         loop {
             let token = self.current_token.clone();
-            if token == Token::EOF || token == Token::RParen || token == Token::Comma {
+            if token == Token::EOF || token == Token::RParen || token == Token::Comma || token == Token::Colon {
                 break;
             }
 
@@ -46,12 +56,14 @@ impl<'a> Parser<'a> {
         Ok(left)
     }
 
-    fn nud(&mut self) -> Result<Expr, String> {
+    fn nud(&mut self) -> Result<Expr, Error> {
         let token = self.current_token.clone();
+        let token_span = self.current_span;
         self.advance()?;
 
         match token {
             Token::Number(n) => Ok(Expr::Number(n)),
+            Token::Str(s) => Ok(Expr::Str(s)),
             Token::Identifier(name) => {
                 if self.current_token == Token::LParen {
                     self.advance()?;
@@ -67,32 +79,61 @@ impl<'a> Parser<'a> {
                         }
                     }
                     if self.current_token != Token::RParen {
-                        return Err("Expected ')' after function arguments".to_string());
+                        return Err(self.error_here(Error::missing_function_closing_paren(
+                            &name,
+                            token_span.start,
+                        )));
                     }
                     self.advance()?;
                     Ok(Expr::Call { func: name, args })
                 } else {
-                    Ok(Expr::Variable(name))
+                    match name.as_str() {
+                        "inf" | "Infinity" => Ok(Expr::Number(f64::INFINITY)),
+                        "nan" => Ok(Expr::Number(f64::NAN)),
+                        _ => Ok(Expr::Variable(name)),
+                    }
                 }
             }
+            Token::OpRef(op) => Ok(Expr::OpFunc(op)),
             Token::Minus => {
                 let ((), r_bp) = self.bp_prefix(&Token::Minus);
                 let expr = self.expression(r_bp)?;
                 Ok(Expr::Unary { op: UnaryOp::Neg, expr: Box::new(expr) })
             }
+            Token::Bang => {
+                let ((), r_bp) = self.bp_prefix(&Token::Bang);
+                let expr = self.expression(r_bp)?;
+                Ok(Expr::Unary { op: UnaryOp::Not, expr: Box::new(expr) })
+            }
             Token::LParen => {
                 let expr = self.expression(0)?;
                 if self.current_token != Token::RParen {
-                    return Err("Expected ')'".to_string());
+                    return Err(self.error_here(Error::missing_closing_paren(token_span.start)));
                 }
                 self.advance()?;
                 Ok(expr)
             }
-            _ => Err(format!("Unexpected token in nud: {:?}", token)),
+            _ => Err(self.error_here(Error::unexpected_token(&format!("{:?}", token), token_span.start))),
         }
     }
 
-    fn led(&mut self, left: Expr, token: Token, r_bp: u8) -> Result<Expr, String> {
+    fn led(&mut self, left: Expr, token: Token, r_bp: u8) -> Result<Expr, Error> {
+        // The ternary `?:` doesn't follow the usual "parse one right operand" shape:
+        // the then-branch is parsed as if parenthesized (min_bp 0) and terminated by `:`.
+        if token == Token::Question {
+            let then = self.expression(0)?;
+            if self.current_token != Token::Colon {
+                return Err(self.error_here(Error::expected_token(
+                    "':'",
+                    &format!("{:?}", self.current_token),
+                    self.current_span.start,
+                )));
+            }
+            self.advance()?;
+            let else_ = self.expression(r_bp)?;
+            return Ok(Expr::Cond { test: Box::new(left), then: Box::new(then), else_: Box::new(else_) });
+        }
+
         let right = self.expression(r_bp)?;
         match token {
             Token::Plus => Ok(Expr::Binary { op: BinaryOp::Add, left: Box::new(left), right: Box::new(right) }),
@@ -100,12 +141,25 @@ impl<'a> Parser<'a> {
             Token::Star => Ok(Expr::Binary { op: BinaryOp::Mul, left: Box::new(left), right: Box::new(right) }),
             Token::Slash => Ok(Expr::Binary { op: BinaryOp::Div, left: Box::new(left), right: Box::new(right) }),
             Token::Caret => Ok(Expr::Binary { op: BinaryOp::Pow, left: Box::new(left), right: Box::new(right) }),
-            _ => Err(format!("Unexpected token in led: {:?}", token)),
+            Token::Lt => Ok(Expr::Binary { op: BinaryOp::Lt, left: Box::new(left), right: Box::new(right) }),
+            Token::Gt => Ok(Expr::Binary { op: BinaryOp::Gt, left: Box::new(left), right: Box::new(right) }),
+            Token::Le => Ok(Expr::Binary { op: BinaryOp::Le, left: Box::new(left), right: Box::new(right) }),
+            Token::Ge => Ok(Expr::Binary { op: BinaryOp::Ge, left: Box::new(left), right: Box::new(right) }),
+            Token::EqEq => Ok(Expr::Binary { op: BinaryOp::Eq, left: Box::new(left), right: Box::new(right) }),
+            Token::NotEq => Ok(Expr::Binary { op: BinaryOp::Ne, left: Box::new(left), right: Box::new(right) }),
+            Token::AndAnd => Ok(Expr::Binary { op: BinaryOp::And, left: Box::new(left), right: Box::new(right) }),
+            Token::OrOr => Ok(Expr::Binary { op: BinaryOp::Or, left: Box::new(left), right: Box::new(right) }),
+            _ => Err(self.error_here(Error::unexpected_token(&format!("{:?}", token), self.current_span.start))),
         }
     }
 
     fn bp_infix(&self, token: &Token) -> (u8, u8) {
         match token {
+            Token::Question => (2, 1), // Right associative, binds loosest
+            Token::OrOr => (3, 4),
+            Token::AndAnd => (5, 6),
+            Token::EqEq | Token::NotEq => (7, 8),
+            Token::Lt | Token::Gt | Token::Le | Token::Ge => (9, 10),
             Token::Plus | Token::Minus => (10, 11),
             Token::Star | Token::Slash => (20, 21),
             Token::Caret => (30, 29), // Right associative
@@ -115,7 +169,7 @@ impl<'a> Parser<'a> {
 
     fn bp_prefix(&self, token: &Token) -> ((), u8) {
         match token {
-            Token::Minus => ((), 99),
+            Token::Minus | Token::Bang => ((), 99),
             _ => ((), 0),
         }
     }
@@ -125,7 +179,7 @@ impl<'a> Parser<'a> {
 mod tests {
     use super::*;
 
-    fn parse(input: &str) -> Result<Expr, String> {
+    fn parse(input: &str) -> Result<Expr, Error> {
         let lexer = Lexer::new(input);
         let mut parser = Parser::new(lexer)?;
         parser.parse()
@@ -153,7 +207,7 @@ mod tests {
     fn test_parse_precedence() {
         // 2 + 3 * 4 should parse as 2 + (3 * 4)
         let expr = parse("2 + 3 * 4").unwrap();
-        
+
         if let Expr::Binary { op: BinaryOp::Add, left, right } = expr {
             assert_eq!(*left, Expr::Number(2.0));
             assert!(matches!(*right, Expr::Binary { op: BinaryOp::Mul, .. }));
@@ -166,7 +220,7 @@ mod tests {
     fn test_parse_right_associativity() {
         // 2 ^ 3 ^ 4 should parse as 2 ^ (3 ^ 4)
         let expr = parse("2 ^ 3 ^ 4").unwrap();
-        
+
         if let Expr::Binary { op: BinaryOp::Pow, left, right } = expr {
             assert_eq!(*left, Expr::Number(2.0));
             assert!(matches!(*right, Expr::Binary { op: BinaryOp::Pow, .. }));
@@ -179,7 +233,7 @@ mod tests {
     fn test_parse_parentheses() {
         // (2 + 3) * 4 should parse as (2 + 3) * 4
         let expr = parse("(2 + 3) * 4").unwrap();
-        
+
         if let Expr::Binary { op: BinaryOp::Mul, left, right } = expr {
             assert!(matches!(*left, Expr::Binary { op: BinaryOp::Add, .. }));
             assert_eq!(*right, Expr::Number(4.0));
@@ -194,10 +248,40 @@ mod tests {
         assert!(matches!(expr, Expr::Unary { op: UnaryOp::Neg, .. }));
     }
 
+    #[test]
+    fn test_parse_infinity_and_nan_constants() {
+        assert_eq!(parse("inf").unwrap(), Expr::Number(f64::INFINITY));
+        assert_eq!(parse("Infinity").unwrap(), Expr::Number(f64::INFINITY));
+        assert!(matches!(parse("nan").unwrap(), Expr::Number(n) if n.is_nan()));
+        // Other identifiers are unaffected.
+        assert_eq!(parse("infinite").unwrap(), Expr::Variable("infinite".to_string()));
+    }
+
+    #[test]
+    fn test_parse_string_literal() {
+        assert_eq!(parse(r#""hello""#).unwrap(), Expr::Str("hello".to_string()));
+    }
+
+    #[test]
+    fn test_parse_operator_reference() {
+        assert_eq!(parse("\\+").unwrap(), Expr::OpFunc(BinaryOp::Add));
+    }
+
+    #[test]
+    fn test_parse_operator_reference_as_call_argument() {
+        let expr = parse("reduce(\\+, 0, x)").unwrap();
+        if let Expr::Call { func, args } = expr {
+            assert_eq!(func, "reduce");
+            assert_eq!(args[0], Expr::OpFunc(BinaryOp::Add));
+        } else {
+            panic!("Expected Call expression");
+        }
+    }
+
     #[test]
     fn test_parse_function_call() {
         let expr = parse("sin(x)").unwrap();
-        
+
         if let Expr::Call { func, args } = expr {
             assert_eq!(func, "sin");
             assert_eq!(args.len(), 1);
@@ -210,7 +294,7 @@ mod tests {
     #[test]
     fn test_parse_function_multiple_args() {
         let expr = parse("max(1, 2, 3)").unwrap();
-        
+
         if let Expr::Call { func, args } = expr {
             assert_eq!(func, "max");
             assert_eq!(args.len(), 3);
@@ -222,7 +306,7 @@ mod tests {
     #[test]
     fn test_parse_function_no_args() {
         let expr = parse("rand()").unwrap();
-        
+
         if let Expr::Call { func, args } = expr {
             assert_eq!(func, "rand");
             assert_eq!(args.len(), 0);
@@ -240,7 +324,7 @@ mod tests {
     #[test]
     fn test_parse_nested_functions() {
         let expr = parse("sin(cos(x))").unwrap();
-        
+
         if let Expr::Call { func, args } = expr {
             assert_eq!(func, "sin");
             assert!(matches!(args[0], Expr::Call { .. }));
@@ -261,6 +345,14 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_error_has_position_and_source() {
+        let err = parse("sin(x").unwrap_err();
+        let display = err.to_string();
+        assert!(display.contains("line 1"));
+        assert!(display.contains("sin(x"));
+    }
+
     #[test]
     fn test_parse_all_operators() {
         assert!(parse("1 + 2").is_ok());
@@ -270,12 +362,74 @@ mod tests {
         assert!(parse("1 ^ 2").is_ok());
     }
 
+    #[test]
+    fn test_parse_comparison() {
+        let expr = parse("x > 0").unwrap();
+        assert!(matches!(expr, Expr::Binary { op: BinaryOp::Gt, .. }));
+    }
+
+    #[test]
+    fn test_parse_boolean_operators() {
+        assert!(matches!(parse("a && b").unwrap(), Expr::Binary { op: BinaryOp::And, .. }));
+        assert!(matches!(parse("a || b").unwrap(), Expr::Binary { op: BinaryOp::Or, .. }));
+        assert!(matches!(parse("!a").unwrap(), Expr::Unary { op: UnaryOp::Not, .. }));
+    }
+
+    #[test]
+    fn test_parse_comparison_precedence() {
+        // 1 + 2 < 3 + 4 should parse as (1 + 2) < (3 + 4)
+        let expr = parse("1 + 2 < 3 + 4").unwrap();
+        if let Expr::Binary { op: BinaryOp::Lt, left, right } = expr {
+            assert!(matches!(*left, Expr::Binary { op: BinaryOp::Add, .. }));
+            assert!(matches!(*right, Expr::Binary { op: BinaryOp::Add, .. }));
+        } else {
+            panic!("Expected Lt at top level");
+        }
+    }
+
+    #[test]
+    fn test_parse_ternary() {
+        // x > 0 ? sqrt(x) : -x
+        let expr = parse("x > 0 ? sqrt(x) : -x").unwrap();
+        if let Expr::Cond { test, then, else_ } = expr {
+            assert!(matches!(*test, Expr::Binary { op: BinaryOp::Gt, .. }));
+            assert!(matches!(*then, Expr::Call { .. }));
+            assert!(matches!(*else_, Expr::Unary { op: UnaryOp::Neg, .. }));
+        } else {
+            panic!("Expected Cond at top level");
+        }
+    }
+
+    #[test]
+    fn test_parse_ternary_right_associative() {
+        // a ? b : c ? d : e should parse as a ? b : (c ? d : e)
+        let expr = parse("a ? b : c ? d : e").unwrap();
+        if let Expr::Cond { else_, .. } = expr {
+            assert!(matches!(*else_, Expr::Cond { .. }));
+        } else {
+            panic!("Expected Cond at top level");
+        }
+    }
+
+    #[test]
+    fn test_parse_boolean_precedence() {
+        // a < b || c > d && e should parse as a < b || (c > d && e), since
+        // && binds tighter than ||, and both bind looser than comparisons.
+        let expr = parse("a < b || c > d && e").unwrap();
+        if let Expr::Binary { op: BinaryOp::Or, left, right } = expr {
+            assert!(matches!(*left, Expr::Binary { op: BinaryOp::Lt, .. }));
+            assert!(matches!(*right, Expr::Binary { op: BinaryOp::And, .. }));
+        } else {
+            panic!("Expected Or at top level");
+        }
+    }
+
     #[test]
     fn test_parse_whitespace_insensitive() {
         let expr1 = parse("1+2").unwrap();
         let expr2 = parse("1 + 2").unwrap();
         let expr3 = parse("  1  +  2  ").unwrap();
-        
+
         assert_eq!(expr1, expr2);
         assert_eq!(expr2, expr3);
     }
@@ -0,0 +1,136 @@
+//! Register-based alternative to `bytecode`'s stack machine.
+//!
+//! `Program`/`VM::run` spend a good fraction of their time on the `push`/
+//! `pop` bookkeeping every `Add`/`Sub`/`Mul`/... does against an implicit
+//! stack. `RegisterProgram` instead compiles straight-line expressions into
+//! three-address instructions (`Add { dst, lhs, rhs }`, `LoadVar { dst,
+//! var_idx }`, ...) whose operands are indices into a flat register file,
+//! assigned by a linear-scan allocator over the expression tree
+//! (`compiler::Compiler::compile_registers`). `VM::run_registers` then reads
+//! and writes that file directly, with no stack traffic and no per-operand
+//! bounds-checked push/pop.
+//!
+//! This is a narrower format than `Program`: it has no encoding for branches
+//! or recursive calls, so ternary `?:`, short-circuiting `&&`/`||`, and
+//! calls to user-defined functions all fail to compile here and need the
+//! ordinary stack-based `Program` instead.
+
+use crate::bytecode::BuiltinFn;
+use crate::vm::{Context, VM};
+
+/// A single three-address instruction. Every operand is a register index
+/// (into the flat file `VM::run_registers` allocates), not a stack position.
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum RegisterOp {
+    LoadConst { dst: u16, value: f64 },
+    LoadVar { dst: u16, var_idx: u16 },
+    Add { dst: u16, lhs: u16, rhs: u16 },
+    Sub { dst: u16, lhs: u16, rhs: u16 },
+    Mul { dst: u16, lhs: u16, rhs: u16 },
+    Div { dst: u16, lhs: u16, rhs: u16 },
+    Pow { dst: u16, lhs: u16, rhs: u16 },
+    Lt { dst: u16, lhs: u16, rhs: u16 },
+    Gt { dst: u16, lhs: u16, rhs: u16 },
+    Le { dst: u16, lhs: u16, rhs: u16 },
+    Ge { dst: u16, lhs: u16, rhs: u16 },
+    Eq { dst: u16, lhs: u16, rhs: u16 },
+    Ne { dst: u16, lhs: u16, rhs: u16 },
+    Neg { dst: u16, src: u16 },
+    Not { dst: u16, src: u16 },
+    /// Samples fresh on every dispatch, same rationale as `OpCode::Rand` —
+    /// there's nowhere to carry RNG state through a bare `BuiltinFn`.
+    Rand { dst: u16 },
+    Randn { dst: u16 },
+    /// `args_base..args_base + arg_count` must be contiguous registers,
+    /// guaranteed by `Compiler::compile_registers`'s allocator always
+    /// assigning a call's arguments back-to-back before freeing them.
+    Call { dst: u16, func_idx: u16, args_base: u16, arg_count: u8 },
+}
+
+/// Bump allocator over a register file: `alloc` hands out the next free
+/// index, and `free` reclaims it for reuse only when it's the
+/// most-recently-allocated one still live. That's exactly what a
+/// tree-shaped expression needs — a subexpression's temporaries are always
+/// freed before its parent's result register is allocated — so plain LIFO
+/// reuse keeps the file as small as a general live-range-tracking linear
+/// scan would, without the bookkeeping one requires.
+#[derive(Default)]
+pub(crate) struct RegisterAllocator {
+    next_free: u16,
+    high_water: u16,
+}
+
+impl RegisterAllocator {
+    pub(crate) fn alloc(&mut self) -> u16 {
+        let reg = self.next_free;
+        self.next_free += 1;
+        self.high_water = self.high_water.max(self.next_free);
+        reg
+    }
+
+    /// Frees `reg`, but only if it's the top of the live range (`reg + 1 ==
+    /// next_free`) — freeing anything else would leave a hole this simple
+    /// allocator can't track, so it's a deliberate no-op instead.
+    pub(crate) fn free(&mut self, reg: u16) {
+        if reg + 1 == self.next_free {
+            self.next_free = reg;
+        }
+    }
+
+    /// The register a fresh `alloc()` would return right now — used to
+    /// mark where a call's argument registers begin.
+    pub(crate) fn next_free(&self) -> u16 {
+        self.next_free
+    }
+
+    /// One past the largest register index ever live, i.e. the size the
+    /// register file needs to be allocated at.
+    pub(crate) fn register_count(&self) -> u16 {
+        self.high_water
+    }
+}
+
+/// A compiled register-based program, produced by
+/// `Compiler::compile_registers` and run with `RegisterProgram::eval` (or
+/// directly via `VM::run_registers`).
+pub struct RegisterProgram {
+    pub(crate) ops: Vec<RegisterOp>,
+    pub(crate) register_count: u16,
+    pub(crate) func_table: Vec<BuiltinFn>,
+    var_names: Vec<String>,
+    pub(crate) result: u16,
+}
+
+impl RegisterProgram {
+    pub(crate) fn new(
+        ops: Vec<RegisterOp>,
+        register_count: u16,
+        func_table: Vec<BuiltinFn>,
+        var_names: Vec<String>,
+        result: u16,
+    ) -> Self {
+        Self {
+            ops,
+            register_count,
+            func_table,
+            var_names,
+            result,
+        }
+    }
+
+    /// Evaluate the program with the given context. Same contract as
+    /// `Program::eval`: errors on a missing context variable or a division
+    /// by zero.
+    pub fn eval(&self, context: &Context) -> Result<f64, String> {
+        VM::run_registers(self, context)
+    }
+
+    /// Create a context pre-sized for this program.
+    pub fn create_context(&self) -> Context {
+        Context::with_capacity(self.var_names.len())
+    }
+
+    pub(crate) fn var_names(&self) -> &[String] {
+        &self.var_names
+    }
+}
@@ -0,0 +1,116 @@
+/// A minimal permuted-congruential generator (PCG32, XSH-RR variant),
+/// backing `OpCode::Rand`/`OpCode::Randn` and `Program::simulate`. Each step
+/// advances a 64-bit LCG state (`state = state * 6364136223846793005 +
+/// increment`) and outputs the xorshift-rotated high bits as a 32-bit word —
+/// small, dependency-free, and good enough for Monte Carlo sampling without
+/// pulling in an external `rand` crate.
+pub(crate) struct Pcg32 {
+    state: u64,
+    inc: u64,
+}
+
+const PCG_MULTIPLIER: u64 = 6364136223846793005;
+const PCG_DEFAULT_STREAM: u64 = 1442695040888963407;
+
+impl Pcg32 {
+    /// Seeds the generator deterministically from a single `u64` seed, using
+    /// the standard PCG initialization sequence (one warm-up step, fold in
+    /// the seed, then another step) so nearby seeds don't produce
+    /// correlated early outputs.
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Self {
+            state: 0,
+            inc: (PCG_DEFAULT_STREAM << 1) | 1,
+        };
+        rng.step();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.step();
+        rng
+    }
+
+    /// Seeds from process entropy (wall-clock time folded with a process-
+    /// wide counter), so a bare `rand()`/`randn()` outside of
+    /// `Program::simulate`'s explicit-seed path still draws a fresh value
+    /// on every `eval()`.
+    pub fn from_entropy() -> Self {
+        use std::sync::atomic::{AtomicU64, Ordering};
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+        let counter = COUNTER.fetch_add(1, Ordering::Relaxed);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos ^ counter.wrapping_mul(0x9E3779B97F4A7C15))
+    }
+
+    fn step(&mut self) -> u64 {
+        let old = self.state;
+        self.state = old.wrapping_mul(PCG_MULTIPLIER).wrapping_add(self.inc);
+        old
+    }
+
+    /// The next raw 32-bit output word.
+    fn next_u32(&mut self) -> u32 {
+        let old = self.step();
+        let xorshifted = (((old >> 18) ^ old) >> 27) as u32;
+        let rot = (old >> 59) as u32;
+        xorshifted.rotate_right(rot)
+    }
+
+    /// Uniform sample in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u32() as f64) / (u32::MAX as f64 + 1.0)
+    }
+
+    /// Standard normal sample via the Box-Muller transform
+    /// (`sqrt(-2*ln(u1)) * cos(2*pi*u2)`).
+    pub fn next_normal(&mut self) -> f64 {
+        let u1 = self.next_f64().max(f64::MIN_POSITIVE);
+        let u2 = self.next_f64();
+        (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_is_deterministic() {
+        let mut a = Pcg32::new(42);
+        let mut b = Pcg32::new(42);
+        for _ in 0..10 {
+            assert_eq!(a.next_u32(), b.next_u32());
+        }
+    }
+
+    #[test]
+    fn test_different_seeds_diverge() {
+        let mut a = Pcg32::new(1);
+        let mut b = Pcg32::new(2);
+        let seq_a: Vec<u32> = (0..10).map(|_| a.next_u32()).collect();
+        let seq_b: Vec<u32> = (0..10).map(|_| b.next_u32()).collect();
+        assert_ne!(seq_a, seq_b);
+    }
+
+    #[test]
+    fn test_next_f64_is_within_unit_interval() {
+        let mut rng = Pcg32::new(7);
+        for _ in 0..1000 {
+            let v = rng.next_f64();
+            assert!((0.0..1.0).contains(&v));
+        }
+    }
+
+    #[test]
+    fn test_next_normal_has_roughly_zero_mean_unit_variance() {
+        let mut rng = Pcg32::new(123);
+        let n = 20_000;
+        let samples: Vec<f64> = (0..n).map(|_| rng.next_normal()).collect();
+        let mean = samples.iter().sum::<f64>() / n as f64;
+        let variance = samples.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / n as f64;
+        assert!(mean.abs() < 0.05, "mean = {}", mean);
+        assert!((variance - 1.0).abs() < 0.1, "variance = {}", variance);
+    }
+}
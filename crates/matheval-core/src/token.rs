@@ -1,7 +1,11 @@
+use crate::ast::BinaryOp;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Token {
     Number(f64),
     Identifier(String),
+    Str(String),
+    OpRef(BinaryOp), // \+, \-, \*, \/, \^, \<, \>
     Plus,       // +
     Minus,      // -
     Star,       // *
@@ -10,6 +14,19 @@ pub enum Token {
     LParen,     // (
     RParen,     // )
     Comma,      // ,
+    Lt,         // <
+    Gt,         // >
+    Le,         // <=
+    Ge,         // >=
+    EqEq,       // ==
+    NotEq,      // !=
+    AndAnd,     // &&
+    OrOr,       // ||
+    Bang,       // !
+    Question,   // ?
+    Colon,      // :
+    Assign,     // =
+    Semicolon,  // ;
     EOF,
 }
 
@@ -51,6 +68,19 @@ mod tests {
             Token::LParen,
             Token::RParen,
             Token::Comma,
+            Token::Lt,
+            Token::Gt,
+            Token::Le,
+            Token::Ge,
+            Token::EqEq,
+            Token::NotEq,
+            Token::AndAnd,
+            Token::OrOr,
+            Token::Bang,
+            Token::Question,
+            Token::Colon,
+            Token::Assign,
+            Token::OpRef(BinaryOp::Add),
             Token::EOF,
         ];
         
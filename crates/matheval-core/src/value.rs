@@ -0,0 +1,67 @@
+/// A runtime value produced by a literal: either a number or a string.
+///
+/// `Expr::Number`/`Expr::Str` both lower to a `Value` so future compiler and
+/// VM work can treat the two uniformly (e.g. a function call that accepts or
+/// returns either). The bytecode VM's stack is still `f64`-only today, so
+/// only the `Num` side is actually compiled; `Str` exists as groundwork for
+/// text-returning builtins like `concat`/`substr`/`format`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Num(f64),
+    Str(String),
+}
+
+impl Value {
+    pub fn as_num(&self) -> Option<f64> {
+        match self {
+            Value::Num(n) => Some(*n),
+            Value::Str(_) => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::Str(s) => Some(s),
+            Value::Num(_) => None,
+        }
+    }
+}
+
+/// Lowers a literal `Expr` into its `Value`, or `None` for non-literal
+/// expressions (variables, calls, etc. have no single value until
+/// evaluated).
+pub fn literal_value(expr: &crate::ast::Expr) -> Option<Value> {
+    match expr {
+        crate::ast::Expr::Number(n) => Some(Value::Num(*n)),
+        crate::ast::Expr::Str(s) => Some(Value::Str(s.clone())),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Expr;
+
+    #[test]
+    fn test_value_as_num() {
+        assert_eq!(Value::Num(1.5).as_num(), Some(1.5));
+        assert_eq!(Value::Str("x".to_string()).as_num(), None);
+    }
+
+    #[test]
+    fn test_value_as_str() {
+        assert_eq!(Value::Str("hi".to_string()).as_str(), Some("hi"));
+        assert_eq!(Value::Num(1.0).as_str(), None);
+    }
+
+    #[test]
+    fn test_literal_value() {
+        assert_eq!(literal_value(&Expr::Number(3.0)), Some(Value::Num(3.0)));
+        assert_eq!(
+            literal_value(&Expr::Str("hi".to_string())),
+            Some(Value::Str("hi".to_string()))
+        );
+        assert_eq!(literal_value(&Expr::Variable("x".to_string())), None);
+    }
+}
@@ -1,4 +1,18 @@
-use crate::bytecode::{OpCode, Program};
+use crate::bytecode::{BuiltinFn, OpCode, Program};
+use crate::packed::{DecodeInstruction, PackedProgram};
+use crate::registers::{RegisterOp, RegisterProgram};
+use crate::rng::Pcg32;
+
+/// Booleans are represented as `0.0`/`1.0` f64 values throughout the VM.
+#[inline]
+fn is_truthy(value: f64) -> bool {
+    value != 0.0
+}
+
+#[inline]
+fn bool_to_f64(value: bool) -> f64 {
+    if value { 1.0 } else { 0.0 }
+}
 
 /// Optimized execution context using indexed array for O(1) variable access
 #[derive(Debug, Clone)]
@@ -62,6 +76,21 @@ impl Context {
 pub struct VM<'a> {
     program: &'a Program,
     stack: Vec<f64>,
+    /// User-defined function bodies shared across a call chain. Always the
+    /// root program's `user_funcs` table, even for a callee's own VM, so
+    /// recursive and mutually-recursive `CallUser` dispatch can resolve.
+    user_funcs: &'a [Program],
+    /// Current nesting depth of `CallUser` dispatch (0 for the top-level run).
+    depth: u32,
+    max_call_depth: u32,
+    /// Backing stream for `OpCode::Rand`/`OpCode::Randn`. Fresh entropy by
+    /// default; `run_simulate` reseeds it deterministically before its run.
+    rng: Pcg32,
+    /// Remaining instruction budget for `run`/`run_batch`, set via
+    /// `with_limit`. `None` (the default) means unlimited — the check this
+    /// adds to the hot loop is then just a cheap `Option` match per
+    /// instruction, with no counter to maintain.
+    max_steps: Option<u64>,
 }
 
 impl<'a> VM<'a> {
@@ -69,10 +98,395 @@ impl<'a> VM<'a> {
         Self {
             program,
             stack: Vec::with_capacity(32),
+            user_funcs: &program.user_funcs,
+            depth: 0,
+            max_call_depth: program.max_call_depth,
+            rng: Pcg32::from_entropy(),
+            max_steps: None,
+        }
+    }
+
+    /// Caps the number of instructions `run`/`run_batch` will dispatch
+    /// before giving up with an error, so evaluating an expression from an
+    /// untrusted source (deeply nested `Pow`, a huge batch) can't cost more
+    /// than the caller is willing to pay. One dispatched instruction is one
+    /// unit of budget, regardless of opcode.
+    ///
+    /// # Example
+    /// ```
+    /// use matheval_core::{Compiler, Context};
+    ///
+    /// let compiler = Compiler::new();
+    /// let program = compiler.compile("x + x + x + x + x").unwrap();
+    /// let mut ctx = Context::new();
+    /// ctx.set_by_index(0, 1.0);
+    /// let result = program.eval_with_limit(&ctx, 1);
+    /// assert!(result.unwrap_err().contains("operation budget exceeded"));
+    /// ```
+    pub fn with_limit(mut self, max_steps: u64) -> Self {
+        self.max_steps = Some(max_steps);
+        self
+    }
+
+    /// Construct a VM for a `CallUser` callee, inheriting the shared
+    /// `user_funcs` table and depth tracking from the caller.
+    fn for_call(program: &'a Program, user_funcs: &'a [Program], depth: u32, max_call_depth: u32) -> Self {
+        Self {
+            program,
+            stack: Vec::with_capacity(32),
+            user_funcs,
+            depth,
+            max_call_depth,
+            rng: Pcg32::from_entropy(),
+            max_steps: None,
+        }
+    }
+
+    /// Decrements the remaining step budget (if one was set via
+    /// `with_limit`), erroring once it's exhausted. Called once per
+    /// dispatched instruction by `execute` and `run_batch`'s scalar path.
+    #[inline]
+    fn charge_step(&mut self) -> Result<(), String> {
+        self.charge_steps(1)
+    }
+
+    /// Decrements the remaining step budget (if one was set via
+    /// `with_limit`) by `n` in one call, erroring if fewer than `n` remain.
+    /// Used by `run_batch_simd`, where one dispatched instruction computes
+    /// `chunk_len` rows at once (1 to `SIMD_LANES`) instead of the scalar
+    /// path's one row per instruction, so its real cost is `n` steps, not 1.
+    #[inline]
+    fn charge_steps(&mut self, n: u64) -> Result<(), String> {
+        if let Some(remaining) = self.max_steps.as_mut() {
+            if *remaining < n {
+                return Err("operation budget exceeded".to_string());
+            }
+            *remaining -= n;
         }
+        Ok(())
     }
 
     pub fn run(&mut self, context: &Context) -> Result<f64, String> {
+        self.execute(context)?;
+        self.pop()
+    }
+
+    /// Runs a program compiled by `Compiler::compile_multi`: executes it the
+    /// same way `run` does, but collects every value left on the stack
+    /// afterward (one per output expression, in order) instead of popping
+    /// just the last one.
+    pub fn run_multi(&mut self, context: &Context) -> Result<Vec<f64>, String> {
+        self.execute(context)?;
+        let expected = self.program.output_count;
+        if self.stack.len() != expected {
+            return Err(format!(
+                "Expected {} output value{}, but the program left {} on the stack",
+                expected,
+                if expected == 1 { "" } else { "s" },
+                self.stack.len()
+            ));
+        }
+        Ok(std::mem::take(&mut self.stack))
+    }
+
+    /// Runs a `RegisterProgram` (see its module doc and
+    /// `Compiler::compile_registers`): a flat `Vec<f64>` register file,
+    /// sized to `register_program.register_count`, is indexed directly by
+    /// each `RegisterOp`'s operands — no stack, so no per-operation
+    /// push/pop or stack-underflow bounds check. Doesn't need a `VM`
+    /// instance (a register program has no jumps or user-function calls to
+    /// track depth/RNG state across), so this is a plain associated
+    /// function rather than a `&mut self` method.
+    pub fn run_registers(register_program: &RegisterProgram, context: &Context) -> Result<f64, String> {
+        let var_names = register_program.var_names();
+        if context.values().len() < var_names.len() {
+            return Err(format!(
+                "Context missing variables: expected {}, got {}",
+                var_names.len(),
+                context.values().len()
+            ));
+        }
+
+        let var_values = context.values();
+        let mut registers = vec![0.0f64; register_program.register_count as usize];
+        let mut rng = Pcg32::from_entropy();
+
+        for op in &register_program.ops {
+            match *op {
+                RegisterOp::LoadConst { dst, value } => registers[dst as usize] = value,
+                RegisterOp::LoadVar { dst, var_idx } => {
+                    registers[dst as usize] = var_values[var_idx as usize];
+                }
+                RegisterOp::Add { dst, lhs, rhs } => {
+                    registers[dst as usize] = registers[lhs as usize] + registers[rhs as usize];
+                }
+                RegisterOp::Sub { dst, lhs, rhs } => {
+                    registers[dst as usize] = registers[lhs as usize] - registers[rhs as usize];
+                }
+                RegisterOp::Mul { dst, lhs, rhs } => {
+                    registers[dst as usize] = registers[lhs as usize] * registers[rhs as usize];
+                }
+                RegisterOp::Div { dst, lhs, rhs } => {
+                    let divisor = registers[rhs as usize];
+                    if divisor == 0.0 {
+                        return Err("Division by zero".to_string());
+                    }
+                    registers[dst as usize] = registers[lhs as usize] / divisor;
+                }
+                RegisterOp::Pow { dst, lhs, rhs } => {
+                    registers[dst as usize] = registers[lhs as usize].powf(registers[rhs as usize]);
+                }
+                RegisterOp::Lt { dst, lhs, rhs } => {
+                    registers[dst as usize] = bool_to_f64(registers[lhs as usize] < registers[rhs as usize]);
+                }
+                RegisterOp::Gt { dst, lhs, rhs } => {
+                    registers[dst as usize] = bool_to_f64(registers[lhs as usize] > registers[rhs as usize]);
+                }
+                RegisterOp::Le { dst, lhs, rhs } => {
+                    registers[dst as usize] = bool_to_f64(registers[lhs as usize] <= registers[rhs as usize]);
+                }
+                RegisterOp::Ge { dst, lhs, rhs } => {
+                    registers[dst as usize] = bool_to_f64(registers[lhs as usize] >= registers[rhs as usize]);
+                }
+                RegisterOp::Eq { dst, lhs, rhs } => {
+                    registers[dst as usize] = bool_to_f64(registers[lhs as usize] == registers[rhs as usize]);
+                }
+                RegisterOp::Ne { dst, lhs, rhs } => {
+                    registers[dst as usize] = bool_to_f64(registers[lhs as usize] != registers[rhs as usize]);
+                }
+                RegisterOp::Neg { dst, src } => registers[dst as usize] = -registers[src as usize],
+                RegisterOp::Not { dst, src } => {
+                    registers[dst as usize] = bool_to_f64(!is_truthy(registers[src as usize]));
+                }
+                RegisterOp::Rand { dst } => registers[dst as usize] = rng.next_f64(),
+                RegisterOp::Randn { dst } => registers[dst as usize] = rng.next_normal(),
+                RegisterOp::Call { dst, func_idx, args_base, arg_count } => {
+                    let args_start = args_base as usize;
+                    let args_end = args_start + arg_count as usize;
+                    let func = register_program.func_table[func_idx as usize];
+                    let result = func(&registers[args_start..args_end]);
+                    registers[dst as usize] = result;
+                }
+            }
+        }
+
+        registers
+            .get(register_program.result as usize)
+            .copied()
+            .ok_or_else(|| "register program left no result".to_string())
+    }
+
+    /// Runs a `PackedProgram` (see the `packed` module doc and
+    /// `Program::to_packed`): a fixed-width `u32` word per instruction, `pc`
+    /// advancing one word at a time instead of a variable number of bytes,
+    /// and jump targets that are already word indices. Needs its own `depth`
+    /// parameter (rather than a `VM` instance's field) because a `CallUser`
+    /// recurses by constructing a fresh ordinary `VM` over the callee's
+    /// (unpacked) `Program` and calling `run` on it.
+    pub fn run_packed(packed_program: &PackedProgram, context: &Context) -> Result<f64, String> {
+        Self::run_packed_at_depth(packed_program, context, 0)
+    }
+
+    fn run_packed_at_depth(packed: &PackedProgram, context: &Context, depth: u32) -> Result<f64, String> {
+        if depth > packed.max_call_depth {
+            return Err(format!(
+                "Recursion limit exceeded (max call depth {})",
+                packed.max_call_depth
+            ));
+        }
+
+        let var_names = packed.var_names();
+        if context.values().len() < var_names.len() {
+            return Err(format!(
+                "Context missing variables: expected {}, got {}",
+                var_names.len(),
+                context.values().len()
+            ));
+        }
+
+        let var_values = context.values();
+        let mut stack: Vec<f64> = Vec::with_capacity(32);
+        let mut locals = vec![0.0f64; packed.local_count];
+        let mut rng = Pcg32::from_entropy();
+        let mut pc = 0usize;
+
+        while pc < packed.words.len() {
+            let word = packed.words[pc];
+            let opcode = word
+                .opcode()
+                .ok_or_else(|| format!("Unknown packed opcode word: {}", word))?;
+            pc += 1;
+
+            match opcode {
+                OpCode::LoadConst => stack.push(packed.constants[word.ax()]),
+                OpCode::LoadVar => stack.push(var_values[word.ax()]),
+                OpCode::Add => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(a + b);
+                }
+                OpCode::Sub => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(a - b);
+                }
+                OpCode::Mul => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(a * b);
+                }
+                OpCode::Div => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    if b == 0.0 {
+                        return Err("Division by zero".to_string());
+                    }
+                    stack.push(a / b);
+                }
+                OpCode::Pow => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(a.powf(b));
+                }
+                OpCode::Neg => {
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(-a);
+                }
+                OpCode::Lt => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(a < b));
+                }
+                OpCode::Gt => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(a > b));
+                }
+                OpCode::Le => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(a <= b));
+                }
+                OpCode::Ge => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(a >= b));
+                }
+                OpCode::Eq => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(a == b));
+                }
+                OpCode::Ne => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(a != b));
+                }
+                OpCode::And => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(is_truthy(a) && is_truthy(b)));
+                }
+                OpCode::Or => {
+                    let b = stack.pop().ok_or("Stack underflow")?;
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(is_truthy(a) || is_truthy(b)));
+                }
+                OpCode::Not => {
+                    let a = stack.pop().ok_or("Stack underflow")?;
+                    stack.push(bool_to_f64(!is_truthy(a)));
+                }
+                OpCode::Rand => stack.push(rng.next_f64()),
+                OpCode::Randn => stack.push(rng.next_normal()),
+                OpCode::Dup => {
+                    let a = *stack.last().ok_or("Stack underflow")?;
+                    stack.push(a);
+                }
+                OpCode::Swap => {
+                    let len = stack.len();
+                    if len < 2 {
+                        return Err("Stack underflow".to_string());
+                    }
+                    stack.swap(len - 1, len - 2);
+                }
+                OpCode::Pop => {
+                    stack.pop().ok_or("Stack underflow")?;
+                }
+                OpCode::StoreLocal => {
+                    locals[word.ax()] = stack.pop().ok_or("Stack underflow")?;
+                }
+                OpCode::LoadLocal => stack.push(locals[word.ax()]),
+                OpCode::Jump => {
+                    pc = word.ax();
+                }
+                OpCode::JumpIfFalse => {
+                    let cond = stack.pop().ok_or("Stack underflow")?;
+                    if !is_truthy(cond) {
+                        pc = word.ax();
+                    }
+                }
+                OpCode::Call => {
+                    let arg_count = word.b() as usize;
+                    let stack_len = stack.len();
+                    if stack_len < arg_count {
+                        return Err("Stack underflow in function call".to_string());
+                    }
+                    let args_start = stack_len - arg_count;
+                    let func = packed.func_table[word.ax()];
+                    let result = func(&stack[args_start..]);
+                    stack.truncate(args_start);
+                    stack.push(result);
+                }
+                OpCode::CallUser => {
+                    let user_idx = word.ax();
+                    let arg_count = word.b() as usize;
+                    if user_idx >= packed.user_funcs.len() {
+                        return Err(format!("Invalid function index: {}", user_idx));
+                    }
+                    let stack_len = stack.len();
+                    if stack_len < arg_count {
+                        return Err("Stack underflow in function call".to_string());
+                    }
+                    let args_start = stack_len - arg_count;
+                    let mut callee_ctx = Context::with_capacity(arg_count);
+                    for (i, &arg) in stack[args_start..].iter().enumerate() {
+                        callee_ctx.set_by_index(i, arg);
+                    }
+                    stack.truncate(args_start);
+
+                    let callee_program = &packed.user_funcs[user_idx];
+                    let mut callee_vm = VM::for_call(
+                        callee_program,
+                        &packed.user_funcs,
+                        depth + 1,
+                        packed.max_call_depth,
+                    );
+                    let result = callee_vm.run(&callee_ctx)?;
+                    stack.push(result);
+                }
+                OpCode::LoadConstU8
+                | OpCode::LoadVarU8
+                | OpCode::CallU8
+                | OpCode::StoreLocalU8
+                | OpCode::LoadLocalU8 => {
+                    unreachable!("from_program folds every *U8 short form into its wide opcode")
+                }
+            }
+        }
+
+        stack.pop().ok_or_else(|| "Stack underflow".to_string())
+    }
+
+    /// Runs the instruction stream to completion, leaving its result(s) on
+    /// the stack — `run` pops exactly one, `run_multi` drains all of them.
+    fn execute(&mut self, context: &Context) -> Result<(), String> {
+        if self.depth > self.max_call_depth {
+            return Err(format!(
+                "Recursion limit exceeded (max call depth {})",
+                self.max_call_depth
+            ));
+        }
+
         // Ensure context has all required variables
         if context.values.len() < self.program.var_names.len() {
             return Err(format!(
@@ -86,38 +500,59 @@ impl<'a> VM<'a> {
         let instructions = &self.program.instructions;
         let constants = &self.program.constants;
         let func_table = &self.program.func_table;
+        let mut locals = vec![0.0f64; self.program.local_count];
 
         let mut pc = 0; // Program counter
-        
+
         while pc < instructions.len() {
-            let opcode = instructions[pc];
+            self.charge_step()?;
+            let byte = instructions[pc];
+            // `Program::validate_opcodes` (run at `from_bytes` load time) is
+            // the only place a bad byte could slip in — anything compiled by
+            // `Compiler` is well-formed by construction — so decoding into
+            // the enum once here and matching on it directly (instead of a
+            // `match opcode { op if op == OpCode::X as u8 => ... }` guard
+            // chain) lets the compiler emit a dense jump table over the hot
+            // loop instead of a linear scan of comparisons.
+            let opcode = OpCode::from_u8(byte)
+                .ok_or_else(|| crate::error::ErrorKind::UnknownOpcode(byte).to_string())?;
             pc += 1;
 
             match opcode {
-                op if op == OpCode::LoadConst as u8 => {
+                OpCode::LoadConst => {
                     let idx = self.read_u16(instructions, &mut pc);
                     self.stack.push(constants[idx as usize]);
                 }
-                op if op == OpCode::LoadVar as u8 => {
+                OpCode::LoadConstU8 => {
+                    let idx = instructions[pc] as usize;
+                    pc += 1;
+                    self.stack.push(constants[idx]);
+                }
+                OpCode::LoadVar => {
                     let idx = self.read_u16(instructions, &mut pc);
                     self.stack.push(var_values[idx as usize]);
                 }
-                op if op == OpCode::Add as u8 => {
+                OpCode::LoadVarU8 => {
+                    let idx = instructions[pc] as usize;
+                    pc += 1;
+                    self.stack.push(var_values[idx]);
+                }
+                OpCode::Add => {
                     let b = self.pop()?;
                     let a = self.pop()?;
                     self.stack.push(a + b);
                 }
-                op if op == OpCode::Sub as u8 => {
+                OpCode::Sub => {
                     let b = self.pop()?;
                     let a = self.pop()?;
                     self.stack.push(a - b);
                 }
-                op if op == OpCode::Mul as u8 => {
+                OpCode::Mul => {
                     let b = self.pop()?;
                     let a = self.pop()?;
                     self.stack.push(a * b);
                 }
-                op if op == OpCode::Div as u8 => {
+                OpCode::Div => {
                     let b = self.pop()?;
                     let a = self.pop()?;
                     if b == 0.0 {
@@ -125,16 +560,109 @@ impl<'a> VM<'a> {
                     }
                     self.stack.push(a / b);
                 }
-                op if op == OpCode::Pow as u8 => {
+                OpCode::Pow => {
                     let b = self.pop()?;
                     let a = self.pop()?;
                     self.stack.push(a.powf(b));
                 }
-                op if op == OpCode::Neg as u8 => {
+                OpCode::Neg => {
                     let a = self.pop()?;
                     self.stack.push(-a);
                 }
-                op if op == OpCode::Call as u8 => {
+                OpCode::Lt => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(a < b));
+                }
+                OpCode::Gt => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(a > b));
+                }
+                OpCode::Le => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(a <= b));
+                }
+                OpCode::Ge => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(a >= b));
+                }
+                OpCode::Eq => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(a == b));
+                }
+                OpCode::Ne => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(a != b));
+                }
+                OpCode::And => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(is_truthy(a) && is_truthy(b)));
+                }
+                OpCode::Or => {
+                    let b = self.pop()?;
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(is_truthy(a) || is_truthy(b)));
+                }
+                OpCode::Not => {
+                    let a = self.pop()?;
+                    self.stack.push(bool_to_f64(!is_truthy(a)));
+                }
+                OpCode::Rand => {
+                    self.stack.push(self.rng.next_f64());
+                }
+                OpCode::Randn => {
+                    self.stack.push(self.rng.next_normal());
+                }
+                OpCode::Dup => {
+                    let a = *self.stack.last().ok_or("Stack underflow")?;
+                    self.stack.push(a);
+                }
+                OpCode::Swap => {
+                    let len = self.stack.len();
+                    if len < 2 {
+                        return Err("Stack underflow".to_string());
+                    }
+                    self.stack.swap(len - 1, len - 2);
+                }
+                OpCode::Pop => {
+                    self.pop()?;
+                }
+                OpCode::StoreLocal => {
+                    let idx = self.read_u16(instructions, &mut pc) as usize;
+                    locals[idx] = self.pop()?;
+                }
+                OpCode::StoreLocalU8 => {
+                    let idx = instructions[pc] as usize;
+                    pc += 1;
+                    locals[idx] = self.pop()?;
+                }
+                OpCode::LoadLocal => {
+                    let idx = self.read_u16(instructions, &mut pc) as usize;
+                    self.stack.push(locals[idx]);
+                }
+                OpCode::LoadLocalU8 => {
+                    let idx = instructions[pc] as usize;
+                    pc += 1;
+                    self.stack.push(locals[idx]);
+                }
+                OpCode::Jump => {
+                    let target = self.read_u16(instructions, &mut pc);
+                    pc = target as usize;
+                }
+                OpCode::JumpIfFalse => {
+                    let target = self.read_u16(instructions, &mut pc);
+                    let cond = self.pop()?;
+                    if !is_truthy(cond) {
+                        pc = target as usize;
+                    }
+                }
+                OpCode::Call => {
                     let func_idx = self.read_u16(instructions, &mut pc) as usize;
                     let arg_count = instructions[pc] as usize;
                     pc += 1;
@@ -148,19 +676,70 @@ impl<'a> VM<'a> {
                     if stack_len < arg_count {
                         return Err("Stack underflow in function call".to_string());
                     }
-                    
+
                     let args_start = stack_len - arg_count;
                     let result = func_table[func_idx](&self.stack[args_start..]);
-                    
+
                     // Pop args and push result
                     self.stack.truncate(args_start);
                     self.stack.push(result);
                 }
-                _ => return Err(format!("Unknown opcode: {}", opcode)),
+                OpCode::CallU8 => {
+                    let func_idx = instructions[pc] as usize;
+                    pc += 1;
+                    let arg_count = instructions[pc] as usize;
+                    pc += 1;
+
+                    if func_idx >= func_table.len() {
+                        return Err(format!("Invalid function index: {}", func_idx));
+                    }
+
+                    let stack_len = self.stack.len();
+                    if stack_len < arg_count {
+                        return Err("Stack underflow in function call".to_string());
+                    }
+
+                    let args_start = stack_len - arg_count;
+                    let result = func_table[func_idx](&self.stack[args_start..]);
+
+                    self.stack.truncate(args_start);
+                    self.stack.push(result);
+                }
+                OpCode::CallUser => {
+                    let user_idx = self.read_u16(instructions, &mut pc) as usize;
+                    let arg_count = instructions[pc] as usize;
+                    pc += 1;
+
+                    if user_idx >= self.user_funcs.len() {
+                        return Err(format!("Invalid function index: {}", user_idx));
+                    }
+
+                    let stack_len = self.stack.len();
+                    if stack_len < arg_count {
+                        return Err("Stack underflow in function call".to_string());
+                    }
+
+                    let args_start = stack_len - arg_count;
+                    let mut callee_ctx = Context::with_capacity(arg_count);
+                    for (i, &arg) in self.stack[args_start..].iter().enumerate() {
+                        callee_ctx.set_by_index(i, arg);
+                    }
+                    self.stack.truncate(args_start);
+
+                    let callee_program = &self.user_funcs[user_idx];
+                    let mut callee_vm = VM::for_call(
+                        callee_program,
+                        self.user_funcs,
+                        self.depth + 1,
+                        self.max_call_depth,
+                    );
+                    let result = callee_vm.run(&callee_ctx)?;
+                    self.stack.push(result);
+                }
             }
         }
 
-        self.pop()
+        Ok(())
     }
 
     #[inline]
@@ -176,78 +755,102 @@ impl<'a> VM<'a> {
         self.stack.pop().ok_or_else(|| "Stack underflow".to_string())
     }
 
-    /// Batch evaluation: evaluate the same program with multiple variable sets
-    /// This is much more efficient than calling eval() in a loop
-    /// 
-    /// # Arguments
-    /// * `var_sets` - A slice where each inner slice contains variable values for one evaluation
-    ///                Each inner slice must have length equal to program.var_names.len()
-    /// 
-    /// # Returns
-    /// A vector of results, one for each variable set
-    /// 
+    /// Batch/columnar evaluation: evaluate the same program once per row,
+    /// amortizing per-call setup (opcode decode, stack allocation) across
+    /// the whole batch.
+    ///
+    /// * `inputs[v]` is the column of values for variable index `v`
+    ///   (matching `Program::var_names`) across all rows; every column must
+    ///   have length equal to `out.len()`.
+    /// * `out` receives one result per row.
+    ///
     /// # Example
     /// ```ignore
     /// let program = compiler.compile("x * 2 + y").unwrap();
-    /// let var_sets = vec![
-    ///     vec![1.0, 2.0],  // x=1, y=2 -> result: 4
-    ///     vec![3.0, 4.0],  // x=3, y=4 -> result: 10
-    ///     vec![5.0, 6.0],  // x=5, y=6 -> result: 16
-    /// ];
-    /// let results = program.eval_batch(&var_sets).unwrap();
+    /// let xs = [1.0, 3.0, 5.0];
+    /// let ys = [2.0, 4.0, 6.0];
+    /// let mut out = [0.0; 3];
+    /// program.eval_batch(&[&xs, &ys], &mut out).unwrap();
+    /// assert_eq!(out, [4.0, 10.0, 16.0]);
     /// ```
-    pub fn run_batch(&mut self, var_sets: &[&[f64]]) -> Result<Vec<f64>, String> {
+    pub fn run_batch(&mut self, inputs: &[&[f64]], out: &mut [f64]) -> Result<(), String> {
         let expected_var_count = self.program.var_names.len();
-        let mut results = Vec::with_capacity(var_sets.len());
+        if inputs.len() != expected_var_count {
+            return Err(format!(
+                "Expected {} variable{}, but got {}",
+                expected_var_count,
+                if expected_var_count == 1 { "" } else { "s" },
+                inputs.len()
+            ));
+        }
 
-        for (i, var_values) in var_sets.iter().enumerate() {
-            if var_values.len() != expected_var_count {
+        let row_count = out.len();
+        for (v, column) in inputs.iter().enumerate() {
+            if column.len() != row_count {
                 return Err(format!(
-                    "Variable set {} has {} values, expected {}",
-                    i,
-                    var_values.len(),
-                    expected_var_count
+                    "Variable {} has {} values, expected {} (matching `out`)",
+                    v,
+                    column.len(),
+                    row_count
                 ));
             }
+        }
 
-            // Reset stack for each evaluation
-            self.stack.clear();
+        let ops = decode_ops(&self.program.instructions, &self.program.constants, &self.program.func_table)?;
 
-            let instructions = &self.program.instructions;
-            let constants = &self.program.constants;
-            let func_table = &self.program.func_table;
+        // `Jump`/`JumpIfFalse` let different rows take different paths
+        // through the instruction stream, and `CallUser` recurses into a
+        // fresh scalar `VM` per call — neither has a natural per-lane
+        // meaning under one shared `pc`, so programs using them still run
+        // one row at a time. Everything else (arithmetic, comparisons,
+        // booleans, locals, and builtin `Call`s) is branch-free and runs
+        // `SIMD_LANES` rows at once instead.
+        let needs_scalar_path = ops.iter().any(|op| {
+            matches!(
+                op,
+                DecodedOp::Jump(_) | DecodedOp::JumpIfFalse(_) | DecodedOp::CallUser(..)
+            )
+        });
 
-            let mut pc = 0;
+        if needs_scalar_path {
+            self.run_batch_scalar(&ops, inputs, out)
+        } else {
+            self.run_batch_simd(&ops, inputs, out)
+        }
+    }
 
-            while pc < instructions.len() {
-                let opcode = instructions[pc];
-                pc += 1;
+    fn run_batch_scalar(&mut self, ops: &[DecodedOp], inputs: &[&[f64]], out: &mut [f64]) -> Result<(), String> {
+        let row_count = out.len();
+        let user_funcs = self.user_funcs;
+        let depth = self.depth;
+        let max_call_depth = self.max_call_depth;
+        let mut locals = vec![0.0f64; self.program.local_count];
 
-                match opcode {
-                    op if op == OpCode::LoadConst as u8 => {
-                        let idx = self.read_u16(instructions, &mut pc);
-                        self.stack.push(constants[idx as usize]);
-                    }
-                    op if op == OpCode::LoadVar as u8 => {
-                        let idx = self.read_u16(instructions, &mut pc);
-                        self.stack.push(var_values[idx as usize]);
-                    }
-                    op if op == OpCode::Add as u8 => {
+        for row in 0..row_count {
+            self.stack.clear();
+            let mut pc = 0;
+
+            while pc < ops.len() {
+                self.charge_step()?;
+                match ops[pc] {
+                    DecodedOp::LoadConst(value) => self.stack.push(value),
+                    DecodedOp::LoadVar(idx) => self.stack.push(inputs[idx as usize][row]),
+                    DecodedOp::Add => {
                         let b = self.pop()?;
                         let a = self.pop()?;
                         self.stack.push(a + b);
                     }
-                    op if op == OpCode::Sub as u8 => {
+                    DecodedOp::Sub => {
                         let b = self.pop()?;
                         let a = self.pop()?;
                         self.stack.push(a - b);
                     }
-                    op if op == OpCode::Mul as u8 => {
+                    DecodedOp::Mul => {
                         let b = self.pop()?;
                         let a = self.pop()?;
                         self.stack.push(a * b);
                     }
-                    op if op == OpCode::Div as u8 => {
+                    DecodedOp::Div => {
                         let b = self.pop()?;
                         let a = self.pop()?;
                         if b == 0.0 {
@@ -255,55 +858,926 @@ impl<'a> VM<'a> {
                         }
                         self.stack.push(a / b);
                     }
-                    op if op == OpCode::Pow as u8 => {
+                    DecodedOp::Pow => {
                         let b = self.pop()?;
                         let a = self.pop()?;
                         self.stack.push(a.powf(b));
                     }
-                    op if op == OpCode::Neg as u8 => {
+                    DecodedOp::Neg => {
                         let a = self.pop()?;
                         self.stack.push(-a);
                     }
-                    op if op == OpCode::Call as u8 => {
-                        let func_idx = self.read_u16(instructions, &mut pc) as usize;
-                        let arg_count = instructions[pc] as usize;
-                        pc += 1;
-
-                        if func_idx >= func_table.len() {
-                            return Err(format!("Invalid function index: {}", func_idx));
-                        }
-
-                        let stack_len = self.stack.len();
-                        if stack_len < arg_count {
-                            return Err("Stack underflow in function call".to_string());
-                        }
-
-                        let args_start = stack_len - arg_count;
-                        let result = func_table[func_idx](&self.stack[args_start..]);
-
-                        self.stack.truncate(args_start);
-                        self.stack.push(result);
+                    DecodedOp::Lt => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(a < b));
                     }
-                    _ => return Err(format!("Unknown opcode: {}", opcode)),
-                }
-            }
-
-            results.push(self.pop()?);
-        }
-
-        Ok(results)
-    }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_context_creation() {
-        let ctx = Context::new();
-        assert_eq!(ctx.values.len(), 0);
-
+                    DecodedOp::Gt => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(a > b));
+                    }
+                    DecodedOp::Le => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(a <= b));
+                    }
+                    DecodedOp::Ge => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(a >= b));
+                    }
+                    DecodedOp::Eq => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(a == b));
+                    }
+                    DecodedOp::Ne => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(a != b));
+                    }
+                    DecodedOp::And => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(is_truthy(a) && is_truthy(b)));
+                    }
+                    DecodedOp::Or => {
+                        let b = self.pop()?;
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(is_truthy(a) || is_truthy(b)));
+                    }
+                    DecodedOp::Not => {
+                        let a = self.pop()?;
+                        self.stack.push(bool_to_f64(!is_truthy(a)));
+                    }
+                    DecodedOp::Rand => {
+                        self.stack.push(self.rng.next_f64());
+                    }
+                    DecodedOp::Randn => {
+                        self.stack.push(self.rng.next_normal());
+                    }
+                    DecodedOp::Dup => {
+                        let a = *self.stack.last().ok_or("Stack underflow")?;
+                        self.stack.push(a);
+                    }
+                    DecodedOp::Swap => {
+                        let len = self.stack.len();
+                        if len < 2 {
+                            return Err("Stack underflow".to_string());
+                        }
+                        self.stack.swap(len - 1, len - 2);
+                    }
+                    DecodedOp::Pop => {
+                        self.pop()?;
+                    }
+                    DecodedOp::StoreLocal(idx) => {
+                        locals[idx as usize] = self.pop()?;
+                    }
+                    DecodedOp::LoadLocal(idx) => {
+                        self.stack.push(locals[idx as usize]);
+                    }
+                    DecodedOp::Jump(target) => {
+                        pc = target;
+                        continue;
+                    }
+                    DecodedOp::JumpIfFalse(target) => {
+                        let cond = self.pop()?;
+                        if !is_truthy(cond) {
+                            pc = target;
+                            continue;
+                        }
+                    }
+                    DecodedOp::Call(func, arg_count) => {
+                        let arg_count = arg_count as usize;
+                        let stack_len = self.stack.len();
+                        if stack_len < arg_count {
+                            return Err("Stack underflow in function call".to_string());
+                        }
+                        let args_start = stack_len - arg_count;
+                        let result = func(&self.stack[args_start..]);
+                        self.stack.truncate(args_start);
+                        self.stack.push(result);
+                    }
+                    DecodedOp::CallUser(user_idx, arg_count) => {
+                        let user_idx = user_idx as usize;
+                        let arg_count = arg_count as usize;
+
+                        if user_idx >= user_funcs.len() {
+                            return Err(format!("Invalid function index: {}", user_idx));
+                        }
+
+                        let stack_len = self.stack.len();
+                        if stack_len < arg_count {
+                            return Err("Stack underflow in function call".to_string());
+                        }
+
+                        let args_start = stack_len - arg_count;
+                        let mut callee_ctx = Context::with_capacity(arg_count);
+                        for (i, &arg) in self.stack[args_start..].iter().enumerate() {
+                            callee_ctx.set_by_index(i, arg);
+                        }
+                        self.stack.truncate(args_start);
+
+                        let callee_program = &user_funcs[user_idx];
+                        let mut callee_vm =
+                            VM::for_call(callee_program, user_funcs, depth + 1, max_call_depth);
+                        let result = callee_vm.run(&callee_ctx)?;
+                        self.stack.push(result);
+                    }
+                }
+                pc += 1;
+            }
+
+            out[row] = self.pop()?;
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates `ops` `SIMD_LANES` rows at a time using a structure-of-
+    /// arrays stack (each slot is a `[f64; SIMD_LANES]`, one lane per row):
+    /// `LoadConst` broadcasts the constant to every lane, `LoadVar` gathers
+    /// lane `k`'s value for that variable, and the arithmetic/comparison/
+    /// boolean ops apply element-wise. This turns `row_count` interpreter
+    /// passes into `ceil(row_count / SIMD_LANES)` passes.
+    ///
+    /// There's no stable `std::simd` to reach for here, so each "vector" is
+    /// a plain fixed-size array — the compiler still has a good shot at
+    /// autovectorizing the per-lane loops, and it needs no nightly feature.
+    ///
+    /// The final chunk is padded by replaying its last real lane into the
+    /// unused ones, so every builtin still sees a finite, in-domain input;
+    /// padding lanes are simply not written back to `out`.
+    ///
+    /// `Div` can fail per lane (divide by zero): rather than aborting the
+    /// whole chunk mid-instruction, the offending lane is set to `NaN` and
+    /// its row index recorded, and the first such row (if any, real rows
+    /// only) is reported as an error once the chunk finishes.
+    ///
+    /// Only called by `run_batch` once it's confirmed `ops` has no
+    /// `Jump`/`JumpIfFalse`/`CallUser` — see the comment in that method for
+    /// why those still need the scalar, one-row-at-a-time path.
+    fn run_batch_simd(&mut self, ops: &[DecodedOp], inputs: &[&[f64]], out: &mut [f64]) -> Result<(), String> {
+        const SIMD_LANES: usize = 4;
+
+        let row_count = out.len();
+        let mut locals = vec![[0.0f64; SIMD_LANES]; self.program.local_count];
+        let mut stack: Vec<[f64; SIMD_LANES]> = Vec::new();
+
+        let mut chunk_start = 0;
+        while chunk_start < row_count {
+            let chunk_len = SIMD_LANES.min(row_count - chunk_start);
+            stack.clear();
+            for slot in locals.iter_mut() {
+                *slot = [0.0; SIMD_LANES];
+            }
+            let mut div_by_zero_row: Option<usize> = None;
+
+            for op in ops {
+                self.charge_steps(chunk_len as u64)?;
+                match *op {
+                    DecodedOp::LoadConst(value) => stack.push([value; SIMD_LANES]),
+                    DecodedOp::LoadVar(idx) => {
+                        let column = inputs[idx as usize];
+                        let mut lanes = [0.0; SIMD_LANES];
+                        lanes[..chunk_len].copy_from_slice(&column[chunk_start..chunk_start + chunk_len]);
+                        for lane in chunk_len..SIMD_LANES {
+                            lanes[lane] = lanes[chunk_len - 1];
+                        }
+                        stack.push(lanes);
+                    }
+                    DecodedOp::Add => simd_binary(&mut stack, |a, b| a + b)?,
+                    DecodedOp::Sub => simd_binary(&mut stack, |a, b| a - b)?,
+                    DecodedOp::Mul => simd_binary(&mut stack, |a, b| a * b)?,
+                    DecodedOp::Div => {
+                        let b = simd_pop(&mut stack)?;
+                        let a = simd_pop(&mut stack)?;
+                        let mut result = [0.0; SIMD_LANES];
+                        for lane in 0..SIMD_LANES {
+                            if b[lane] == 0.0 {
+                                if lane < chunk_len && div_by_zero_row.is_none() {
+                                    div_by_zero_row = Some(chunk_start + lane);
+                                }
+                                result[lane] = f64::NAN;
+                            } else {
+                                result[lane] = a[lane] / b[lane];
+                            }
+                        }
+                        stack.push(result);
+                    }
+                    DecodedOp::Pow => simd_binary(&mut stack, |a, b| a.powf(b))?,
+                    DecodedOp::Neg => simd_unary(&mut stack, |a| -a)?,
+                    DecodedOp::Lt => simd_binary(&mut stack, |a, b| bool_to_f64(a < b))?,
+                    DecodedOp::Gt => simd_binary(&mut stack, |a, b| bool_to_f64(a > b))?,
+                    DecodedOp::Le => simd_binary(&mut stack, |a, b| bool_to_f64(a <= b))?,
+                    DecodedOp::Ge => simd_binary(&mut stack, |a, b| bool_to_f64(a >= b))?,
+                    DecodedOp::Eq => simd_binary(&mut stack, |a, b| bool_to_f64(a == b))?,
+                    DecodedOp::Ne => simd_binary(&mut stack, |a, b| bool_to_f64(a != b))?,
+                    DecodedOp::And => {
+                        simd_binary(&mut stack, |a, b| bool_to_f64(is_truthy(a) && is_truthy(b)))?
+                    }
+                    DecodedOp::Or => {
+                        simd_binary(&mut stack, |a, b| bool_to_f64(is_truthy(a) || is_truthy(b)))?
+                    }
+                    DecodedOp::Not => simd_unary(&mut stack, |a| bool_to_f64(!is_truthy(a)))?,
+                    DecodedOp::Rand => {
+                        let mut lanes = [0.0; SIMD_LANES];
+                        for lane in lanes.iter_mut() {
+                            *lane = self.rng.next_f64();
+                        }
+                        stack.push(lanes);
+                    }
+                    DecodedOp::Randn => {
+                        let mut lanes = [0.0; SIMD_LANES];
+                        for lane in lanes.iter_mut() {
+                            *lane = self.rng.next_normal();
+                        }
+                        stack.push(lanes);
+                    }
+                    DecodedOp::Dup => {
+                        let a = *stack.last().ok_or("Stack underflow")?;
+                        stack.push(a);
+                    }
+                    DecodedOp::Swap => {
+                        let len = stack.len();
+                        if len < 2 {
+                            return Err("Stack underflow".to_string());
+                        }
+                        stack.swap(len - 1, len - 2);
+                    }
+                    DecodedOp::Pop => {
+                        simd_pop(&mut stack)?;
+                    }
+                    DecodedOp::StoreLocal(idx) => {
+                        locals[idx as usize] = simd_pop(&mut stack)?;
+                    }
+                    DecodedOp::LoadLocal(idx) => {
+                        stack.push(locals[idx as usize]);
+                    }
+                    DecodedOp::Call(func, arg_count) => {
+                        let arg_count = arg_count as usize;
+                        if stack.len() < arg_count {
+                            return Err("Stack underflow in function call".to_string());
+                        }
+                        let args_start = stack.len() - arg_count;
+                        let arg_lanes = &stack[args_start..];
+                        let mut result = [0.0; SIMD_LANES];
+                        let mut scalar_args = vec![0.0; arg_count];
+                        for lane in 0..SIMD_LANES {
+                            for (i, lanes) in arg_lanes.iter().enumerate() {
+                                scalar_args[i] = lanes[lane];
+                            }
+                            result[lane] = func(&scalar_args);
+                        }
+                        stack.truncate(args_start);
+                        stack.push(result);
+                    }
+                    DecodedOp::Jump(_) | DecodedOp::JumpIfFalse(_) | DecodedOp::CallUser(..) => {
+                        unreachable!("run_batch only takes the SIMD path for jump/CallUser-free programs")
+                    }
+                }
+            }
+
+            if let Some(failing_row) = div_by_zero_row {
+                return Err(format!("Division by zero in row {}", failing_row));
+            }
+
+            let result = simd_pop(&mut stack)?;
+            out[chunk_start..chunk_start + chunk_len].copy_from_slice(&result[..chunk_len]);
+            chunk_start += chunk_len;
+        }
+
+        Ok(())
+    }
+
+    /// Reverse-mode (adjoint) automatic differentiation: returns the
+    /// program's value plus `∂result/∂var` for every variable in
+    /// `Program::var_names`.
+    ///
+    /// Runs a forward pass like `run`, but instead of a plain `f64` stack it
+    /// builds a Wengert tape — one entry per value produced, recording which
+    /// earlier tape slots (if any) it was computed from. Only the
+    /// instructions actually executed are taped, so a ternary/`&&`/`||`'s
+    /// untaken branch simply never appears, same as the forward pass. The
+    /// backward pass then walks the tape in reverse, seeding the output's
+    /// adjoint at `1.0` and applying each op's local derivative (`Add`
+    /// splits the adjoint to both operands, `Mul` routes `adj*other` to
+    /// each, etc. — see the match in the second loop below), accumulating
+    /// into the gradient slot for every `LoadVar`/`LoadVarU8` it reaches.
+    ///
+    /// Not supported yet: `OpCode::CallUser` (differentiating through a
+    /// user-defined function body) errors out. Comparisons/booleans and
+    /// `floor`/`ceil`/`round` are treated as locally flat (zero adjoint to
+    /// their inputs, correct almost everywhere); `max`/`min` and any other
+    /// builtin without a known derivative are treated the same way rather
+    /// than computing a subgradient.
+    pub fn run_grad(&mut self, context: &Context) -> Result<(f64, Vec<f64>), String> {
+        if context.values.len() < self.program.var_names.len() {
+            return Err(format!(
+                "Context missing variables: expected {}, got {}",
+                self.program.var_names.len(),
+                context.values.len()
+            ));
+        }
+
+        let var_values = context.values();
+        let instructions = &self.program.instructions;
+        let constants = &self.program.constants;
+        let func_table = &self.program.func_table;
+        let func_names = &self.program.func_names;
+
+        let mut tape: Vec<TapeEntry> = Vec::new();
+        let mut stack: Vec<usize> = Vec::new();
+        // Each slot just forwards the tape index it was last stored with —
+        // loading it is the same value/node, not a new one, so a
+        // subexpression shared via `compile_multi`'s CSE differentiates
+        // exactly as if it had been written out at every occurrence (the
+        // backward pass below naturally accumulates adjoint contributions
+        // from every consumer of that one node).
+        let mut locals_tape: Vec<usize> = vec![usize::MAX; self.program.local_count];
+        let mut pc = 0;
+
+        while pc < instructions.len() {
+            let byte = instructions[pc];
+            pc += 1;
+            let opcode = OpCode::from_u8(byte).ok_or_else(|| format!("Unknown opcode: {}", byte))?;
+
+            match opcode {
+                OpCode::LoadConst => {
+                    let idx = self.read_u16(instructions, &mut pc);
+                    push_leaf(&mut tape, &mut stack, constants[idx as usize], TapeOp::Const);
+                }
+                OpCode::LoadConstU8 => {
+                    let idx = instructions[pc] as usize;
+                    pc += 1;
+                    push_leaf(&mut tape, &mut stack, constants[idx], TapeOp::Const);
+                }
+                OpCode::LoadVar => {
+                    let idx = self.read_u16(instructions, &mut pc);
+                    push_leaf(&mut tape, &mut stack, var_values[idx as usize], TapeOp::Var(idx));
+                }
+                OpCode::LoadVarU8 => {
+                    let idx = instructions[pc] as u16;
+                    pc += 1;
+                    push_leaf(&mut tape, &mut stack, var_values[idx as usize], TapeOp::Var(idx));
+                }
+                OpCode::Add => {
+                    let (r, l) = pop_pair(&mut stack)?;
+                    let value = tape[l].value + tape[r].value;
+                    push_node(&mut tape, &mut stack, value, TapeOp::Add(l, r));
+                }
+                OpCode::Sub => {
+                    let (r, l) = pop_pair(&mut stack)?;
+                    let value = tape[l].value - tape[r].value;
+                    push_node(&mut tape, &mut stack, value, TapeOp::Sub(l, r));
+                }
+                OpCode::Mul => {
+                    let (r, l) = pop_pair(&mut stack)?;
+                    let value = tape[l].value * tape[r].value;
+                    push_node(&mut tape, &mut stack, value, TapeOp::Mul(l, r));
+                }
+                OpCode::Div => {
+                    let (r, l) = pop_pair(&mut stack)?;
+                    if tape[r].value == 0.0 {
+                        return Err("Division by zero".to_string());
+                    }
+                    let value = tape[l].value / tape[r].value;
+                    push_node(&mut tape, &mut stack, value, TapeOp::Div(l, r));
+                }
+                OpCode::Pow => {
+                    let (r, l) = pop_pair(&mut stack)?;
+                    let value = tape[l].value.powf(tape[r].value);
+                    push_node(&mut tape, &mut stack, value, TapeOp::Pow(l, r));
+                }
+                OpCode::Neg => {
+                    let a = pop_one(&mut stack)?;
+                    let value = -tape[a].value;
+                    push_node(&mut tape, &mut stack, value, TapeOp::Neg(a));
+                }
+                OpCode::Lt | OpCode::Gt | OpCode::Le | OpCode::Ge | OpCode::Eq | OpCode::Ne
+                | OpCode::And | OpCode::Or => {
+                    let (r, l) = pop_pair(&mut stack)?;
+                    let (a, b) = (tape[l].value, tape[r].value);
+                    let value = match opcode {
+                        OpCode::Lt => bool_to_f64(a < b),
+                        OpCode::Gt => bool_to_f64(a > b),
+                        OpCode::Le => bool_to_f64(a <= b),
+                        OpCode::Ge => bool_to_f64(a >= b),
+                        OpCode::Eq => bool_to_f64(a == b),
+                        OpCode::Ne => bool_to_f64(a != b),
+                        OpCode::And => bool_to_f64(is_truthy(a) && is_truthy(b)),
+                        OpCode::Or => bool_to_f64(is_truthy(a) || is_truthy(b)),
+                        _ => unreachable!(),
+                    };
+                    push_leaf(&mut tape, &mut stack, value, TapeOp::Opaque);
+                }
+                OpCode::Not => {
+                    let a = pop_one(&mut stack)?;
+                    let value = bool_to_f64(!is_truthy(tape[a].value));
+                    push_leaf(&mut tape, &mut stack, value, TapeOp::Opaque);
+                }
+                OpCode::Rand => {
+                    let value = self.rng.next_f64();
+                    push_leaf(&mut tape, &mut stack, value, TapeOp::Opaque);
+                }
+                OpCode::Randn => {
+                    let value = self.rng.next_normal();
+                    push_leaf(&mut tape, &mut stack, value, TapeOp::Opaque);
+                }
+                OpCode::Dup => {
+                    let a = *stack.last().ok_or_else(|| "Stack underflow".to_string())?;
+                    stack.push(a);
+                }
+                OpCode::Swap => {
+                    let len = stack.len();
+                    if len < 2 {
+                        return Err("Stack underflow".to_string());
+                    }
+                    stack.swap(len - 1, len - 2);
+                }
+                OpCode::Pop => {
+                    pop_one(&mut stack)?;
+                }
+                OpCode::StoreLocal => {
+                    let idx = self.read_u16(instructions, &mut pc) as usize;
+                    locals_tape[idx] = pop_one(&mut stack)?;
+                }
+                OpCode::StoreLocalU8 => {
+                    let idx = instructions[pc] as usize;
+                    pc += 1;
+                    locals_tape[idx] = pop_one(&mut stack)?;
+                }
+                OpCode::LoadLocal => {
+                    let idx = self.read_u16(instructions, &mut pc) as usize;
+                    stack.push(locals_tape[idx]);
+                }
+                OpCode::LoadLocalU8 => {
+                    let idx = instructions[pc] as usize;
+                    pc += 1;
+                    stack.push(locals_tape[idx]);
+                }
+                OpCode::Jump => {
+                    let target = self.read_u16(instructions, &mut pc);
+                    pc = target as usize;
+                }
+                OpCode::JumpIfFalse => {
+                    let target = self.read_u16(instructions, &mut pc);
+                    let cond = pop_one(&mut stack)?;
+                    if !is_truthy(tape[cond].value) {
+                        pc = target as usize;
+                    }
+                }
+                OpCode::Call | OpCode::CallU8 => {
+                    let func_idx = if opcode == OpCode::Call {
+                        self.read_u16(instructions, &mut pc) as usize
+                    } else {
+                        let idx = instructions[pc] as usize;
+                        pc += 1;
+                        idx
+                    };
+                    let arg_count = instructions[pc] as usize;
+                    pc += 1;
+
+                    if func_idx >= func_table.len() {
+                        return Err(format!("Invalid function index: {}", func_idx));
+                    }
+                    if stack.len() < arg_count {
+                        return Err("Stack underflow in function call".to_string());
+                    }
+
+                    let args_start = stack.len() - arg_count;
+                    let arg_indices: Vec<usize> = stack.split_off(args_start);
+                    let arg_values: Vec<f64> = arg_indices.iter().map(|&i| tape[i].value).collect();
+                    let value = func_table[func_idx](&arg_values);
+
+                    let op = if arg_indices.len() == 1
+                        && unary_builtin_derivative(&func_names[func_idx], arg_values[0]).is_some()
+                    {
+                        TapeOp::Unary(func_names[func_idx].clone(), arg_indices[0])
+                    } else {
+                        TapeOp::Opaque
+                    };
+                    push_leaf(&mut tape, &mut stack, value, op);
+                }
+                OpCode::CallUser => {
+                    return Err(
+                        "eval_grad doesn't support differentiating through a user-defined \
+                         function call yet"
+                            .to_string(),
+                    );
+                }
+            }
+        }
+
+        if stack.len() != 1 {
+            return Err("Stack underflow".to_string());
+        }
+        let result_idx = stack[0];
+        let result = tape[result_idx].value;
+
+        let mut adjoint = vec![0.0f64; tape.len()];
+        adjoint[result_idx] = 1.0;
+
+        for i in (0..tape.len()).rev() {
+            let a = adjoint[i];
+            if a == 0.0 {
+                continue;
+            }
+            match &tape[i].op {
+                TapeOp::Const | TapeOp::Var(_) | TapeOp::Opaque => {}
+                TapeOp::Add(l, r) => {
+                    adjoint[*l] += a;
+                    adjoint[*r] += a;
+                }
+                TapeOp::Sub(l, r) => {
+                    adjoint[*l] += a;
+                    adjoint[*r] -= a;
+                }
+                TapeOp::Mul(l, r) => {
+                    adjoint[*l] += a * tape[*r].value;
+                    adjoint[*r] += a * tape[*l].value;
+                }
+                TapeOp::Div(l, r) => {
+                    let (lv, rv) = (tape[*l].value, tape[*r].value);
+                    adjoint[*l] += a / rv;
+                    adjoint[*r] -= a * lv / (rv * rv);
+                }
+                TapeOp::Pow(l, r) => {
+                    let (lv, rv) = (tape[*l].value, tape[*r].value);
+                    adjoint[*l] += a * rv * lv.powf(rv - 1.0);
+                    adjoint[*r] += a * tape[i].value * lv.ln();
+                }
+                TapeOp::Neg(x) => adjoint[*x] -= a,
+                TapeOp::Unary(name, x) => {
+                    if let Some(d) = unary_builtin_derivative(name, tape[*x].value) {
+                        adjoint[*x] += a * d;
+                    }
+                }
+            }
+        }
+
+        let mut gradient = vec![0.0; self.program.var_names.len()];
+        for (i, entry) in tape.iter().enumerate() {
+            if let TapeOp::Var(idx) = entry.op {
+                gradient[idx as usize] += adjoint[i];
+            }
+        }
+
+        Ok((result, gradient))
+    }
+
+    /// Runs the program `n` times, reseeding this VM's RNG stream
+    /// deterministically from `seed` first, and summarizes the results.
+    /// Reuses this VM/stack across all `n` runs the same way `run_batch`
+    /// reuses its decoded ops across rows — each `run` leaves the stack
+    /// empty on success, so nothing needs resetting between iterations.
+    pub fn run_simulate(
+        &mut self,
+        context: &Context,
+        n: usize,
+        seed: u64,
+    ) -> Result<SimStats, String> {
+        if n == 0 {
+            return Err("simulate requires at least 1 sample".to_string());
+        }
+
+        self.rng = Pcg32::new(seed);
+
+        let mut sum = 0.0;
+        let mut sum_sq = 0.0;
+        for _ in 0..n {
+            let value = self.run(context)?;
+            sum += value;
+            sum_sq += value * value;
+        }
+
+        let count = n as f64;
+        let mean = sum / count;
+        let variance = if n > 1 {
+            (sum_sq - count * mean * mean) / (count - 1.0)
+        } else {
+            0.0
+        };
+        let std_error = (variance / count).sqrt();
+
+        Ok(SimStats {
+            mean,
+            variance,
+            std_error,
+            n,
+        })
+    }
+}
+
+/// Summary statistics from `Program::simulate`'s repeated sampling: the mean
+/// and (sample) variance of the program's result across `n` runs, plus the
+/// standard error of that mean (`sqrt(variance / n)`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SimStats {
+    pub mean: f64,
+    pub variance: f64,
+    pub std_error: f64,
+    pub n: usize,
+}
+
+/// The local derivative of a unary builtin at `x`, or `None` if `name` isn't
+/// a recognized unary builtin with a known closed form (including
+/// `floor`/`ceil`/`round`, whose a.e.-true derivative is `0.0`). `max`/`min`
+/// and anything else fall through to `None` and are taped as `TapeOp::Opaque`.
+fn unary_builtin_derivative(name: &str, x: f64) -> Option<f64> {
+    match name {
+        "sin" => Some(x.cos()),
+        "cos" => Some(-x.sin()),
+        "tan" => Some(1.0 + x.tan() * x.tan()),
+        "sqrt" => Some(0.5 / x.sqrt()),
+        "abs" => Some(if x >= 0.0 { 1.0 } else { -1.0 }),
+        "exp" => Some(x.exp()),
+        "ln" => Some(1.0 / x),
+        "log10" => Some(1.0 / (x * std::f64::consts::LN_10)),
+        "floor" | "ceil" | "round" => Some(0.0),
+        "erf" => Some(2.0 / std::f64::consts::PI.sqrt() * (-x * x).exp()),
+        "erfc" => Some(-2.0 / std::f64::consts::PI.sqrt() * (-x * x).exp()),
+        "norm_cdf" => Some(crate::compiler::builtin_norm_pdf(&[x])),
+        "norm_pdf" => Some(-x * crate::compiler::builtin_norm_pdf(&[x])),
+        _ => None,
+    }
+}
+
+/// One node of the Wengert tape `VM::run_grad` builds during its forward
+/// pass: the value produced at that point, and (for anything but a leaf)
+/// which earlier tape slots it was computed from, for the backward pass to
+/// route adjoints through.
+#[derive(Debug, Clone)]
+struct TapeEntry {
+    value: f64,
+    op: TapeOp,
+}
+
+#[derive(Debug, Clone)]
+enum TapeOp {
+    /// A literal constant — no inputs, no adjoint to propagate.
+    Const,
+    /// A `LoadVar`/`LoadVarU8` — no inputs; its adjoint becomes a gradient
+    /// entry for this variable slot once the backward pass reaches it.
+    Var(u16),
+    Add(usize, usize),
+    Sub(usize, usize),
+    Mul(usize, usize),
+    Div(usize, usize),
+    Pow(usize, usize),
+    Neg(usize),
+    /// A call to a unary builtin with a known derivative (see
+    /// `unary_builtin_derivative`), keyed by name since the tape only has
+    /// `Program::func_names` to identify which builtin ran.
+    Unary(String, usize),
+    /// Comparisons/booleans, `rand`/`randn`, `max`/`min`, or any other
+    /// builtin without a known derivative: treated as locally flat, so no
+    /// adjoint flows to its inputs. (`floor`/`ceil`/`round` are taped as
+    /// `Unary` with a zero derivative instead.)
+    Opaque,
+}
+
+fn push_leaf(tape: &mut Vec<TapeEntry>, stack: &mut Vec<usize>, value: f64, op: TapeOp) {
+    tape.push(TapeEntry { value, op });
+    stack.push(tape.len() - 1);
+}
+
+fn push_node(tape: &mut Vec<TapeEntry>, stack: &mut Vec<usize>, value: f64, op: TapeOp) {
+    push_leaf(tape, stack, value, op);
+}
+
+/// Pops the top two tape-index operands of a binary op, returning
+/// `(right, left)` in the order they were pushed (`left` was pushed first).
+fn pop_pair(stack: &mut Vec<usize>) -> Result<(usize, usize), String> {
+    let r = stack.pop().ok_or_else(|| "Stack underflow".to_string())?;
+    let l = stack.pop().ok_or_else(|| "Stack underflow".to_string())?;
+    Ok((r, l))
+}
+
+fn pop_one(stack: &mut Vec<usize>) -> Result<usize, String> {
+    stack.pop().ok_or_else(|| "Stack underflow".to_string())
+}
+
+/// A single bytecode instruction, fully decoded so that `VM::run_batch` can
+/// skip opcode dispatch and operand/constant/function lookups on every row.
+#[derive(Debug, Clone, Copy)]
+enum DecodedOp {
+    LoadConst(f64),
+    LoadVar(u16),
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Pow,
+    Neg,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Eq,
+    Ne,
+    And,
+    Or,
+    Not,
+    Rand,
+    Randn,
+    Dup,
+    Swap,
+    Pop,
+    StoreLocal(u16),
+    LoadLocal(u16),
+    /// Index into the decoded `ops` vector (not a raw byte offset).
+    Jump(usize),
+    /// Index into the decoded `ops` vector (not a raw byte offset).
+    JumpIfFalse(usize),
+    Call(BuiltinFn, u8),
+    CallUser(u16, u8),
+}
+
+/// Decodes a raw instruction stream into `DecodedOp`s once, resolving
+/// constant values and builtin function pointers up front and translating
+/// `Jump`/`JumpIfFalse` byte-offset targets into indices into the returned
+/// vector.
+fn decode_ops(instructions: &[u8], constants: &[f64], func_table: &[BuiltinFn]) -> Result<Vec<DecodedOp>, String> {
+    let mut ops = Vec::new();
+    let mut offset_to_index = vec![usize::MAX; instructions.len() + 1];
+    let mut jump_fixups = Vec::new();
+    let mut pc = 0;
+
+    while pc < instructions.len() {
+        offset_to_index[pc] = ops.len();
+        let byte = instructions[pc];
+        pc += 1;
+
+        let opcode = OpCode::from_u8(byte).ok_or_else(|| format!("Unknown opcode: {}", byte))?;
+        let op = match opcode {
+            OpCode::LoadConst => {
+                let idx = read_u16_bytes(instructions, &mut pc);
+                let value = *constants
+                    .get(idx as usize)
+                    .ok_or_else(|| format!("Invalid constant index: {}", idx))?;
+                DecodedOp::LoadConst(value)
+            }
+            OpCode::LoadConstU8 => {
+                let idx = instructions[pc] as u16;
+                pc += 1;
+                let value = *constants
+                    .get(idx as usize)
+                    .ok_or_else(|| format!("Invalid constant index: {}", idx))?;
+                DecodedOp::LoadConst(value)
+            }
+            OpCode::LoadVar => DecodedOp::LoadVar(read_u16_bytes(instructions, &mut pc)),
+            OpCode::LoadVarU8 => {
+                let idx = instructions[pc] as u16;
+                pc += 1;
+                DecodedOp::LoadVar(idx)
+            }
+            OpCode::Add => DecodedOp::Add,
+            OpCode::Sub => DecodedOp::Sub,
+            OpCode::Mul => DecodedOp::Mul,
+            OpCode::Div => DecodedOp::Div,
+            OpCode::Pow => DecodedOp::Pow,
+            OpCode::Neg => DecodedOp::Neg,
+            OpCode::Lt => DecodedOp::Lt,
+            OpCode::Gt => DecodedOp::Gt,
+            OpCode::Le => DecodedOp::Le,
+            OpCode::Ge => DecodedOp::Ge,
+            OpCode::Eq => DecodedOp::Eq,
+            OpCode::Ne => DecodedOp::Ne,
+            OpCode::And => DecodedOp::And,
+            OpCode::Or => DecodedOp::Or,
+            OpCode::Not => DecodedOp::Not,
+            OpCode::Rand => DecodedOp::Rand,
+            OpCode::Randn => DecodedOp::Randn,
+            OpCode::Dup => DecodedOp::Dup,
+            OpCode::Swap => DecodedOp::Swap,
+            OpCode::Pop => DecodedOp::Pop,
+            OpCode::StoreLocal => DecodedOp::StoreLocal(read_u16_bytes(instructions, &mut pc)),
+            OpCode::StoreLocalU8 => {
+                let idx = instructions[pc] as u16;
+                pc += 1;
+                DecodedOp::StoreLocal(idx)
+            }
+            OpCode::LoadLocal => DecodedOp::LoadLocal(read_u16_bytes(instructions, &mut pc)),
+            OpCode::LoadLocalU8 => {
+                let idx = instructions[pc] as u16;
+                pc += 1;
+                DecodedOp::LoadLocal(idx)
+            }
+            OpCode::Jump => {
+                let target = read_u16_bytes(instructions, &mut pc);
+                jump_fixups.push((ops.len(), target as usize, false));
+                DecodedOp::Jump(usize::MAX)
+            }
+            OpCode::JumpIfFalse => {
+                let target = read_u16_bytes(instructions, &mut pc);
+                jump_fixups.push((ops.len(), target as usize, true));
+                DecodedOp::JumpIfFalse(usize::MAX)
+            }
+            OpCode::Call => {
+                let func_idx = read_u16_bytes(instructions, &mut pc) as usize;
+                let arg_count = instructions[pc];
+                pc += 1;
+                let func = *func_table
+                    .get(func_idx)
+                    .ok_or_else(|| format!("Invalid function index: {}", func_idx))?;
+                DecodedOp::Call(func, arg_count)
+            }
+            OpCode::CallU8 => {
+                let func_idx = instructions[pc] as usize;
+                pc += 1;
+                let arg_count = instructions[pc];
+                pc += 1;
+                let func = *func_table
+                    .get(func_idx)
+                    .ok_or_else(|| format!("Invalid function index: {}", func_idx))?;
+                DecodedOp::Call(func, arg_count)
+            }
+            OpCode::CallUser => {
+                let user_idx = read_u16_bytes(instructions, &mut pc);
+                let arg_count = instructions[pc];
+                pc += 1;
+                DecodedOp::CallUser(user_idx, arg_count)
+            }
+        };
+        ops.push(op);
+    }
+    offset_to_index[instructions.len()] = ops.len();
+
+    for (op_index, byte_target, is_conditional) in jump_fixups {
+        let index = offset_to_index[byte_target];
+        if index == usize::MAX {
+            return Err(format!("Invalid jump target: {}", byte_target));
+        }
+        ops[op_index] = if is_conditional {
+            DecodedOp::JumpIfFalse(index)
+        } else {
+            DecodedOp::Jump(index)
+        };
+    }
+
+    Ok(ops)
+}
+
+#[inline]
+fn read_u16_bytes(instructions: &[u8], pc: &mut usize) -> u16 {
+    let high = instructions[*pc] as u16;
+    let low = instructions[*pc + 1] as u16;
+    *pc += 2;
+    (high << 8) | low
+}
+
+/// Pops one lane-vector off `VM::run_batch_simd`'s structure-of-arrays
+/// stack, the SIMD-lane equivalent of `VM::pop`.
+#[inline]
+fn simd_pop<const LANES: usize>(stack: &mut Vec<[f64; LANES]>) -> Result<[f64; LANES], String> {
+    stack.pop().ok_or_else(|| "Stack underflow".to_string())
+}
+
+/// Applies a binary op element-wise across two popped lane-vectors and
+/// pushes the result, the same pop-pop-push shape as the scalar stack ops
+/// in `VM::run_batch_scalar` but operating on a whole lane at once.
+#[inline]
+fn simd_binary<const LANES: usize>(
+    stack: &mut Vec<[f64; LANES]>,
+    f: impl Fn(f64, f64) -> f64,
+) -> Result<(), String> {
+    let b = simd_pop(stack)?;
+    let a = simd_pop(stack)?;
+    let mut result = [0.0; LANES];
+    for lane in 0..LANES {
+        result[lane] = f(a[lane], b[lane]);
+    }
+    stack.push(result);
+    Ok(())
+}
+
+/// Applies a unary op element-wise across one popped lane-vector and
+/// pushes the result.
+#[inline]
+fn simd_unary<const LANES: usize>(
+    stack: &mut Vec<[f64; LANES]>,
+    f: impl Fn(f64) -> f64,
+) -> Result<(), String> {
+    let a = simd_pop(stack)?;
+    let mut result = [0.0; LANES];
+    for lane in 0..LANES {
+        result[lane] = f(a[lane]);
+    }
+    stack.push(result);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_creation() {
+        let ctx = Context::new();
+        assert_eq!(ctx.values.len(), 0);
+
         let ctx = Context::with_capacity(5);
         assert_eq!(ctx.values.len(), 5);
     }
@@ -442,6 +1916,199 @@ mod tests {
         assert_eq!(result, 5.0);
     }
 
+    #[test]
+    fn test_vm_comparison() {
+        // Program: 5 > 3
+        let mut program = Program::new();
+        program.constants.push(5.0);
+        program.constants.push(3.0);
+
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 1]);
+        program.instructions.push(OpCode::Gt as u8);
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_vm_dup_duplicates_the_top_of_stack() {
+        // Program: 3 * 3, via Dup instead of loading the constant twice
+        let mut program = Program::new();
+        program.constants.push(3.0);
+
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::Dup as u8);
+        program.instructions.push(OpCode::Mul as u8);
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), 9.0);
+    }
+
+    #[test]
+    fn test_vm_swap_exchanges_the_top_two() {
+        // Stack ends up [5, 2] after the two loads; Swap makes it [2, 5], so
+        // Sub (which pops `b` then `a` and computes `a - b`) computes 2 - 5.
+        let mut program = Program::new();
+        program.constants.push(5.0);
+        program.constants.push(2.0);
+
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 1]);
+        program.instructions.push(OpCode::Swap as u8);
+        program.instructions.push(OpCode::Sub as u8);
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), -3.0);
+    }
+
+    #[test]
+    fn test_vm_pop_discards_the_top_of_stack() {
+        // Program: 1, 2 -> Pop -> 1
+        let mut program = Program::new();
+        program.constants.push(1.0);
+        program.constants.push(2.0);
+
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 1]);
+        program.instructions.push(OpCode::Pop as u8);
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_vm_dup_on_an_empty_stack_is_a_stack_underflow() {
+        let mut program = Program::new();
+        program.instructions.push(OpCode::Dup as u8);
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert!(vm.run(&ctx).unwrap_err().contains("Stack underflow"));
+    }
+
+    #[test]
+    fn test_vm_jump_skips_instructions() {
+        // Program: Jump over a LoadConst(99), landing directly on LoadConst(1)
+        let mut program = Program::new();
+        program.constants.push(99.0);
+        program.constants.push(1.0);
+
+        program.instructions.push(OpCode::Jump as u8);
+        let jump_operand = program.instructions.len();
+        program.instructions.extend_from_slice(&[0, 0]); // placeholder
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]); // const[0] = 99.0 (skipped)
+        let target = program.instructions.len() as u16;
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 1]); // const[1] = 1.0
+
+        program.instructions[jump_operand] = (target >> 8) as u8;
+        program.instructions[jump_operand + 1] = (target & 0xFF) as u8;
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), 1.0);
+    }
+
+    #[test]
+    fn test_vm_jump_if_false_ternary() {
+        // Program: 0 ? 10 : 20 -- JumpIfFalse should branch to the else arm
+        let mut program = Program::new();
+        program.constants.push(0.0);
+        program.constants.push(10.0);
+        program.constants.push(20.0);
+
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]); // push 0.0 (test)
+        program.instructions.push(OpCode::JumpIfFalse as u8);
+        let else_operand = program.instructions.len();
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 1]); // then: 10.0
+        program.instructions.push(OpCode::Jump as u8);
+        let end_operand = program.instructions.len();
+        program.instructions.extend_from_slice(&[0, 0]);
+        let else_target = program.instructions.len() as u16;
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 2]); // else: 20.0
+        let end_target = program.instructions.len() as u16;
+
+        program.instructions[else_operand] = (else_target >> 8) as u8;
+        program.instructions[else_operand + 1] = (else_target & 0xFF) as u8;
+        program.instructions[end_operand] = (end_target >> 8) as u8;
+        program.instructions[end_operand + 1] = (end_target & 0xFF) as u8;
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), 20.0);
+    }
+
+    #[test]
+    fn test_vm_call_user_function() {
+        // square(x) = x ^ 2; main: square(5)
+        let mut square = Program::new();
+        square.var_names.push("x".to_string());
+        square.instructions.push(OpCode::LoadVar as u8);
+        square.instructions.extend_from_slice(&[0, 0]);
+        square.instructions.push(OpCode::LoadConst as u8);
+        square.constants.push(2.0);
+        square.instructions.extend_from_slice(&[0, 0]);
+        square.instructions.push(OpCode::Pow as u8);
+
+        let mut program = Program::new();
+        program.constants.push(5.0);
+        program.user_funcs.push(square);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::CallUser as u8);
+        program.instructions.extend_from_slice(&[0, 0]); // user_func[0]
+        program.instructions.push(1); // 1 arg
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), 25.0);
+    }
+
+    #[test]
+    fn test_vm_call_user_recursion_limit() {
+        // loop(n) = loop(n); main: loop(0)
+        let mut looping = Program::new();
+        looping.var_names.push("n".to_string());
+        looping.instructions.push(OpCode::LoadVar as u8);
+        looping.instructions.extend_from_slice(&[0, 0]);
+        looping.instructions.push(OpCode::CallUser as u8);
+        looping.instructions.extend_from_slice(&[0, 0]); // user_func[0] (itself)
+        looping.instructions.push(1);
+
+        let mut program = Program::new();
+        program.constants.push(0.0);
+        program.max_call_depth = 4;
+        program.user_funcs.push(looping);
+        program.instructions.push(OpCode::LoadConst as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(OpCode::CallUser as u8);
+        program.instructions.extend_from_slice(&[0, 0]);
+        program.instructions.push(1);
+
+        let ctx = Context::new();
+        let mut vm = VM::new(&program);
+        let result = vm.run(&ctx);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("Recursion limit exceeded"));
+    }
+
     #[test]
     fn test_vm_stack_underflow() {
         let mut program = Program::new();
@@ -450,7 +2117,189 @@ mod tests {
         let ctx = Context::new();
         let mut vm = VM::new(&program);
         let result = vm.run(&ctx);
-        
+
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_with_limit_stops_a_program_that_exceeds_the_budget() {
+        let program = crate::Compiler::new().compile("x + x + x + x + x").unwrap();
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 1.0);
+
+        let mut vm = VM::new(&program).with_limit(1);
+        let result = vm.run(&ctx);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("operation budget exceeded"));
+    }
+
+    #[test]
+    fn test_with_limit_allows_a_program_within_the_budget() {
+        let program = crate::Compiler::new().compile("x + x + x + x + x").unwrap();
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 1.0);
+
+        let mut vm = VM::new(&program).with_limit(1_000);
+        assert_eq!(vm.run(&ctx).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_with_limit_applies_to_run_batch_across_the_whole_batch() {
+        let program = crate::Compiler::new().compile("x * x").unwrap();
+        let xs = [1.0, 2.0, 3.0, 4.0];
+        let mut out = [0.0; 4];
+
+        // Each row takes 3 instructions (LoadVar, LoadVar, Mul), and there
+        // are 4 rows, so a budget of 3 covers one row but not the 12 steps
+        // the whole batch needs.
+        let mut vm = VM::new(&program).with_limit(3);
+        let result = vm.run_batch(&[&xs], &mut out);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("operation budget exceeded"));
+    }
+
+    #[test]
+    fn test_with_limit_on_run_batch_charges_per_row_not_per_chunk() {
+        // x * x is 3 ops/row (LoadVar, LoadVar, Mul); 4000 rows costs 12000
+        // steps on the scalar path. `run_batch` takes the SIMD path here
+        // (no branches), which processes up to SIMD_LANES=4 rows per
+        // dispatched instruction — if the budget were charged once per
+        // instruction per chunk instead of once per row, this would
+        // undercount by ~4x and wrongly succeed under a budget of 3000.
+        let program = crate::Compiler::new().compile("x * x").unwrap();
+        let xs: Vec<f64> = (0..4000).map(|i| i as f64).collect();
+        let mut out = vec![0.0; xs.len()];
+
+        let mut vm = VM::new(&program).with_limit(3000);
+        let result = vm.run_batch(&[&xs], &mut out);
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("operation budget exceeded"));
+
+        // A budget that actually covers 3 ops * 4000 rows succeeds.
+        let mut vm = VM::new(&program).with_limit(12000);
+        assert!(vm.run_batch(&[&xs], &mut out).is_ok());
+    }
+
+    #[test]
+    fn test_without_limit_runs_unbounded() {
+        let program = crate::Compiler::new().compile("x + x + x + x + x").unwrap();
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 1.0);
+
+        let mut vm = VM::new(&program);
+        assert_eq!(vm.run(&ctx).unwrap(), 5.0);
+    }
+
+    #[test]
+    fn test_run_grad_polynomial() {
+        // d/dx(x^2 + 3x) = 2x + 3
+        let program = crate::Compiler::new().compile("x * x + 3 * x").unwrap();
+        let mut ctx = Context::new();
+        ctx.set_by_index(0, 5.0);
+
+        let (value, grad) = program.eval_grad(&ctx).unwrap();
+        assert_eq!(value, 40.0);
+        assert_eq!(grad, vec![13.0]);
+    }
+
+    #[test]
+    fn test_run_grad_division_and_power() {
+        // f(x, y) = x / y; df/dx = 1/y, df/dy = -x/y^2
+        let program = crate::Compiler::new().compile("x / y").unwrap();
+        let mut ctx = Context::new();
+        ctx.set_by_index(0, 6.0);
+        ctx.set_by_index(1, 2.0);
+
+        let (value, grad) = program.eval_grad(&ctx).unwrap();
+        assert_eq!(value, 3.0);
+        assert_eq!(grad, vec![0.5, -1.5]);
+    }
+
+    #[test]
+    fn test_run_grad_transcendental_functions() {
+        // d/dx(sin(x)) = cos(x)
+        let program = crate::Compiler::new().compile("sin(x)").unwrap();
+        let mut ctx = Context::new();
+        ctx.set_by_index(0, 1.0);
+
+        let (value, grad) = program.eval_grad(&ctx).unwrap();
+        assert!((value - 1.0f64.sin()).abs() < 1e-12);
+        assert!((grad[0] - 1.0f64.cos()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_run_grad_norm_cdf_derivative_is_norm_pdf() {
+        let program = crate::Compiler::new().compile("norm_cdf(x)").unwrap();
+        let mut ctx = Context::new();
+        ctx.set_by_index(0, 0.5);
+
+        let (_, grad) = program.eval_grad(&ctx).unwrap();
+        let expected = crate::compiler::builtin_norm_pdf(&[0.5]);
+        assert!((grad[0] - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_run_grad_ternary_only_taken_branch_gets_gradient() {
+        // x > 0 ? x * x : y * y -- only x's branch runs when x = 3, y = 10
+        let program = crate::Compiler::new()
+            .compile("x > 0 ? x * x : y * y")
+            .unwrap();
+        let mut ctx = Context::new();
+        ctx.set_by_index(0, 3.0);
+        ctx.set_by_index(1, 10.0);
+
+        let (value, grad) = program.eval_grad(&ctx).unwrap();
+        assert_eq!(value, 9.0);
+        assert_eq!(grad[0], 6.0);
+        assert_eq!(grad[1], 0.0);
+    }
+
+    #[test]
+    fn test_run_grad_call_user_function_is_unsupported() {
+        let program = crate::Compiler::new()
+            .compile_unit("square(x) = x ^ 2; square(y)")
+            .unwrap();
+        let mut ctx = program.create_context();
+        ctx.set_by_index(0, 2.0);
+
+        let result = program.eval_grad(&ctx);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_rand_is_within_unit_interval() {
+        let program = crate::Compiler::new().compile("rand()").unwrap();
+        let ctx = Context::new();
+        for _ in 0..100 {
+            let value = program.eval(&ctx).unwrap();
+            assert!((0.0..1.0).contains(&value));
+        }
+    }
+
+    #[test]
+    fn test_simulate_randn_matches_standard_normal_stats() {
+        let program = crate::Compiler::new().compile("randn()").unwrap();
+        let stats = program.simulate(&Context::new(), 20_000, 42).unwrap();
+
+        assert_eq!(stats.n, 20_000);
+        assert!(stats.mean.abs() < 0.05, "mean = {}", stats.mean);
+        assert!((stats.variance - 1.0).abs() < 0.1, "variance = {}", stats.variance);
+    }
+
+    #[test]
+    fn test_simulate_is_deterministic_given_the_same_seed() {
+        let program = crate::Compiler::new()
+            .compile("max(100.0*exp(randn()*0.2) - 100.0, 0)")
+            .unwrap();
+
+        let a = program.simulate(&Context::new(), 500, 7).unwrap();
+        let b = program.simulate(&Context::new(), 500, 7).unwrap();
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_rand_with_arguments_is_a_compile_error() {
+        let result = crate::Compiler::new().compile("rand(1)");
         assert!(result.is_err());
     }
 }
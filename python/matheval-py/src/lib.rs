@@ -20,6 +20,22 @@ impl Compiler {
             Err(e) => Err(pyo3::exceptions::PyValueError::new_err(e)),
         }
     }
+
+    /// Registers a Python-side function so expressions can call it by name.
+    ///
+    /// `matheval_core::BuiltinFn` is a plain `fn(&[f64]) -> f64` pointer, not
+    /// a boxed closure, so it has nowhere to stash a captured `PyObject` or
+    /// the GIL handle needed to call back into Python. `register_fn` on
+    /// `matheval_core::Compiler` takes the same `BuiltinFn`, so this can't
+    /// wire a Python callable through to the compiled program and always
+    /// returns `NotImplementedError`.
+    fn register_fn(&mut self, name: &str, _func: PyObject) -> PyResult<()> {
+        Err(pyo3::exceptions::PyNotImplementedError::new_err(format!(
+            "register_fn('{}', ...): custom Python functions aren't supported yet \
+             because matheval_core::BuiltinFn is a non-capturing function pointer",
+            name
+        )))
+    }
 }
 
 #[pyclass]
@@ -158,6 +174,16 @@ mod tests {
         assert!(var_names.contains(&"z".to_string()));
     }
 
+    #[test]
+    fn test_register_fn_not_yet_supported() {
+        Python::with_gil(|py| {
+            let mut compiler = Compiler::new();
+            let identity = py.eval("lambda x: x", None, None).unwrap().unbind();
+            let result = compiler.register_fn("identity", identity);
+            assert!(result.is_err());
+        });
+    }
+
     #[test]
     fn test_constant_folding_in_python_wrapper() {
         let compiler = Compiler::new();